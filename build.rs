@@ -0,0 +1,27 @@
+use std::env;
+use std::process::Command;
+
+fn main() {
+    // Commit the binary was built from, shown by `GET /version`.
+    // Builds without a git checkout, e.g. in Docker, can pass it as `GIT_HASH`.
+    let git_hash = env::var("GIT_HASH").ok().or_else(|| {
+        Command::new("git")
+            .args(["rev-parse", "--short", "HEAD"])
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .and_then(|output| String::from_utf8(output.stdout).ok())
+            .map(|hash| hash.trim().to_string())
+    });
+
+    println!(
+        "cargo:rustc-env=GIT_HASH={}",
+        git_hash.unwrap_or_else(|| "unknown".to_string())
+    );
+    println!("cargo:rerun-if-env-changed=GIT_HASH");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+
+    // Migrations are embedded by `sqlx::migrate!`
+    println!("cargo:rerun-if-changed=migrations");
+}
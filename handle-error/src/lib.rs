@@ -5,7 +5,11 @@ use sqlx::Error as SqlxError;
 use std::fmt;
 use std::fmt::Display;
 use warp::{
-    body::BodyDeserializeError, filters::cors::CorsForbidden, http::StatusCode, reject::Reject,
+    body::BodyDeserializeError,
+    filters::cors::CorsForbidden,
+    http::{header::RETRY_AFTER, HeaderValue, StatusCode},
+    reject::Reject,
+    reply::WithStatus,
     Rejection, Reply,
 };
 
@@ -24,6 +28,23 @@ pub enum Error {
     WrongPasswordError,
     CannotDecryptToken,
     Unauthorized,
+    AccountNotFound,
+    QuestionNotFound,
+    AnswerNotFound,
+    InsufficientReputation(i32),
+    CannotVoteOwnPost,
+    NotificationNotFound,
+    QuestionClosed,
+    InvalidDuplicate,
+    ModerationItemNotFound,
+    ProfanityRejected(Vec<String>),
+    ProfanityServiceUnavailable,
+    TooManyRequests(RateLimited),
+    WebhookNotFound,
+    WebhookDeliveryNotFound,
+    InvalidWebhook,
+    WebhookDeliveryFailed(String),
+    PreconditionFailed,
 }
 
 #[derive(Debug, Clone)]
@@ -38,6 +59,16 @@ impl Display for APILayerError {
     }
 }
 
+/// Sent as `Retry-After` and `RateLimit-*` headers of `429 Too Many Requests`
+#[derive(Debug, Clone)]
+pub struct RateLimited {
+    pub limit: u32,
+    /// Seconds until the next attempt is allowed
+    pub retry_after: u64,
+    /// Seconds until the limit is fully available again
+    pub reset: u64,
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -52,6 +83,31 @@ impl fmt::Display for Error {
             Error::WrongPasswordError => write!(f, "WrongPassword"),
             Error::CannotDecryptToken => write!(f, "Cannot decrypt token"),
             Error::Unauthorized => write!(f, "Unauthorized"),
+            Error::AccountNotFound => write!(f, "Account not found"),
+            Error::QuestionNotFound => write!(f, "Question not found"),
+            Error::AnswerNotFound => write!(f, "Answer not found"),
+            Error::InsufficientReputation(required) => {
+                write!(f, "At least {} reputation required", required)
+            }
+            Error::CannotVoteOwnPost => write!(f, "Cannot vote on own post"),
+            Error::NotificationNotFound => write!(f, "Notification not found"),
+            Error::QuestionClosed => write!(f, "Question is closed"),
+            Error::InvalidDuplicate => write!(f, "Invalid duplicate question"),
+            Error::ModerationItemNotFound => write!(f, "Moderation item not found"),
+            Error::ProfanityServiceUnavailable => write!(f, "Profanity service unavailable"),
+            Error::WebhookNotFound => write!(f, "Webhook not found"),
+            Error::WebhookDeliveryNotFound => write!(f, "Webhook delivery not found"),
+            Error::InvalidWebhook => write!(f, "Invalid webhook"),
+            Error::WebhookDeliveryFailed(err) => write!(f, "Webhook delivery failed: {}", err),
+            Error::PreconditionFailed => write!(f, "Precondition failed"),
+            Error::TooManyRequests(limited) => write!(
+                f,
+                "Too many requests, retry after {} seconds",
+                limited.retry_after
+            ),
+            Error::ProfanityRejected(words) => {
+                write!(f, "Content contains bad words: {}", words.join(", "))
+            }
         }
     }
 }
@@ -63,6 +119,21 @@ const DUPLICATE_KEY: u32 = 23505;
 
 #[instrument]
 pub async fn return_error(rejection: Rejection) -> Result<impl Reply, Rejection> {
+    let mut response = error_reply(&rejection)?.into_response();
+
+    // Rate limited clients are told when to retry
+    if let Some(Error::TooManyRequests(limited)) = rejection.find() {
+        let headers = response.headers_mut();
+        headers.insert(RETRY_AFTER, HeaderValue::from(limited.retry_after));
+        headers.insert("RateLimit-Limit", HeaderValue::from(limited.limit));
+        headers.insert("RateLimit-Remaining", HeaderValue::from(0));
+        headers.insert("RateLimit-Reset", HeaderValue::from(limited.reset));
+    }
+
+    Ok(response)
+}
+
+fn error_reply(rejection: &Rejection) -> Result<WithStatus<String>, Rejection> {
     if let Some(Error::DatabaseQueryError(e)) = rejection.find() {
         event!(Level::ERROR, "Database query error");
 
@@ -127,6 +198,109 @@ pub async fn return_error(rejection: Rejection) -> Result<impl Reply, Rejection>
             "No permission to change underlying resource".to_string(),
             StatusCode::UNAUTHORIZED,
         ))
+    } else if let Some(Error::CannotDecryptToken) = rejection.find() {
+        event!(Level::WARN, "Cannot decrypt token");
+        Ok(warp::reply::with_status(
+            "Invalid or expired token".to_string(),
+            StatusCode::UNAUTHORIZED,
+        ))
+    } else if let Some(Error::AccountNotFound) = rejection.find() {
+        event!(Level::WARN, "Account not found");
+        Ok(warp::reply::with_status(
+            "Account not found".to_string(),
+            StatusCode::NOT_FOUND,
+        ))
+    } else if let Some(Error::QuestionNotFound) = rejection.find() {
+        event!(Level::WARN, "Question not found");
+        Ok(warp::reply::with_status(
+            "Question not found".to_string(),
+            StatusCode::NOT_FOUND,
+        ))
+    } else if let Some(Error::AnswerNotFound) = rejection.find() {
+        event!(Level::WARN, "Answer not found");
+        Ok(warp::reply::with_status(
+            "Answer not found".to_string(),
+            StatusCode::NOT_FOUND,
+        ))
+    } else if let Some(error @ Error::InsufficientReputation(_)) = rejection.find() {
+        event!(Level::WARN, "{}", error);
+        Ok(warp::reply::with_status(
+            error.to_string(),
+            StatusCode::FORBIDDEN,
+        ))
+    } else if let Some(Error::CannotVoteOwnPost) = rejection.find() {
+        event!(Level::WARN, "Account tried to vote on own post");
+        Ok(warp::reply::with_status(
+            "Cannot vote on own post".to_string(),
+            StatusCode::FORBIDDEN,
+        ))
+    } else if let Some(Error::NotificationNotFound) = rejection.find() {
+        event!(Level::WARN, "Notification not found");
+        Ok(warp::reply::with_status(
+            "Notification not found".to_string(),
+            StatusCode::NOT_FOUND,
+        ))
+    } else if let Some(Error::QuestionClosed) = rejection.find() {
+        event!(Level::WARN, "Question is closed");
+        Ok(warp::reply::with_status(
+            "Question is closed and does not accept new answers".to_string(),
+            StatusCode::CONFLICT,
+        ))
+    } else if let Some(Error::InvalidDuplicate) = rejection.find() {
+        event!(Level::WARN, "Invalid duplicate question");
+        Ok(warp::reply::with_status(
+            "Questions closed as duplicate need an existing, different duplicate_of question"
+                .to_string(),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        ))
+    } else if let Some(Error::ModerationItemNotFound) = rejection.find() {
+        event!(Level::WARN, "Moderation item not found");
+        Ok(warp::reply::with_status(
+            "Moderation item not found".to_string(),
+            StatusCode::NOT_FOUND,
+        ))
+    } else if let Some(error @ Error::ProfanityRejected(_)) = rejection.find() {
+        event!(Level::WARN, "{}", error);
+        Ok(warp::reply::with_status(
+            error.to_string(),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        ))
+    } else if let Some(Error::ProfanityServiceUnavailable) = rejection.find() {
+        event!(Level::ERROR, "Profanity service unavailable");
+        Ok(warp::reply::with_status(
+            "Content cannot be checked right now, please try again later".to_string(),
+            StatusCode::SERVICE_UNAVAILABLE,
+        ))
+    } else if let Some(error @ Error::TooManyRequests(_)) = rejection.find() {
+        event!(Level::INFO, "{}", error);
+        Ok(warp::reply::with_status(
+            error.to_string(),
+            StatusCode::TOO_MANY_REQUESTS,
+        ))
+    } else if let Some(Error::WebhookNotFound) = rejection.find() {
+        event!(Level::WARN, "Webhook not found");
+        Ok(warp::reply::with_status(
+            "Webhook not found".to_string(),
+            StatusCode::NOT_FOUND,
+        ))
+    } else if let Some(Error::WebhookDeliveryNotFound) = rejection.find() {
+        event!(Level::WARN, "Webhook delivery not found");
+        Ok(warp::reply::with_status(
+            "Webhook delivery not found".to_string(),
+            StatusCode::NOT_FOUND,
+        ))
+    } else if let Some(Error::InvalidWebhook) = rejection.find() {
+        event!(Level::WARN, "Invalid webhook");
+        Ok(warp::reply::with_status(
+            "Webhooks need an http or https URL of a public host and at least one event".to_string(),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        ))
+    } else if let Some(Error::PreconditionFailed) = rejection.find() {
+        event!(Level::INFO, "Precondition failed");
+        Ok(warp::reply::with_status(
+            "The resource changed in the meantime, fetch it again before updating it".to_string(),
+            StatusCode::PRECONDITION_FAILED,
+        ))
     } else if let Some(error) = rejection.find::<Error>() {
         event!(Level::ERROR, "{}", error);
         Ok(warp::reply::with_status(
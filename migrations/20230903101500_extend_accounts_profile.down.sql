@@ -0,0 +1,6 @@
+ALTER TABLE accounts
+DROP COLUMN display_name,
+DROP COLUMN bio,
+DROP COLUMN avatar_url,
+DROP COLUMN reputation,
+DROP COLUMN created_on;
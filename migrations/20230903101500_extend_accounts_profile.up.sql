@@ -0,0 +1,6 @@
+ALTER TABLE accounts
+ADD COLUMN display_name VARCHAR(255),
+ADD COLUMN bio TEXT,
+ADD COLUMN avatar_url VARCHAR(2048),
+ADD COLUMN reputation integer NOT NULL DEFAULT 0,
+ADD COLUMN created_on TIMESTAMP NOT NULL DEFAULT NOW();
@@ -0,0 +1,6 @@
+DROP TABLE IF EXISTS reputation_events;
+DROP TABLE IF EXISTS answer_votes;
+DROP TABLE IF EXISTS question_votes;
+
+ALTER TABLE questions
+DROP COLUMN accepted_answer_id;
@@ -0,0 +1,31 @@
+ALTER TABLE questions
+ADD COLUMN accepted_answer_id integer REFERENCES answers ON DELETE SET NULL;
+
+CREATE TABLE IF NOT EXISTS question_votes (
+  account_id integer NOT NULL,
+  question_id integer NOT NULL REFERENCES questions ON DELETE CASCADE,
+  value smallint NOT NULL CHECK (value IN (-1, 1)),
+  created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (account_id, question_id)
+);
+
+CREATE TABLE IF NOT EXISTS answer_votes (
+  account_id integer NOT NULL,
+  answer_id integer NOT NULL REFERENCES answers ON DELETE CASCADE,
+  value smallint NOT NULL CHECK (value IN (-1, 1)),
+  created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (account_id, answer_id)
+);
+
+CREATE TABLE IF NOT EXISTS reputation_events (
+  id serial PRIMARY KEY,
+  account_id integer NOT NULL,
+  kind VARCHAR(64) NOT NULL,
+  points integer NOT NULL,
+  actor_account_id integer NOT NULL,
+  question_id integer,
+  answer_id integer,
+  created_on TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS reputation_events_account_id_idx ON reputation_events (account_id);
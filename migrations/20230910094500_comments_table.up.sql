@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS comments (
+  id serial PRIMARY KEY,
+  content TEXT NOT NULL,
+  question_id integer NOT NULL REFERENCES questions ON DELETE CASCADE,
+  account_id integer NOT NULL,
+  created_on TIMESTAMP NOT NULL DEFAULT NOW()
+);
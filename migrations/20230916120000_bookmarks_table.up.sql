@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS bookmarks (
+  account_id integer NOT NULL,
+  question_id integer NOT NULL REFERENCES questions ON DELETE CASCADE,
+  created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (account_id, question_id)
+);
@@ -0,0 +1,27 @@
+CREATE TABLE IF NOT EXISTS question_follows (
+  account_id integer NOT NULL,
+  question_id integer NOT NULL REFERENCES questions ON DELETE CASCADE,
+  created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (account_id, question_id)
+);
+
+CREATE TABLE IF NOT EXISTS tag_follows (
+  account_id integer NOT NULL,
+  tag VARCHAR(255) NOT NULL,
+  created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (account_id, tag)
+);
+
+CREATE TABLE IF NOT EXISTS notifications (
+  id serial PRIMARY KEY,
+  account_id integer NOT NULL,
+  kind VARCHAR(64) NOT NULL,
+  question_id integer NOT NULL REFERENCES questions ON DELETE CASCADE,
+  answer_id integer REFERENCES answers ON DELETE CASCADE,
+  comment_id integer REFERENCES comments ON DELETE CASCADE,
+  actor_account_id integer NOT NULL,
+  read_on TIMESTAMP,
+  created_on TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS notifications_account_id_idx ON notifications (account_id, created_on);
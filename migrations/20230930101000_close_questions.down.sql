@@ -0,0 +1,10 @@
+ALTER TABLE questions
+DROP CONSTRAINT questions_closed_check,
+DROP COLUMN close_reason,
+DROP COLUMN close_message,
+DROP COLUMN duplicate_of,
+DROP COLUMN closed_by,
+DROP COLUMN closed_on;
+
+ALTER TABLE accounts
+DROP COLUMN is_moderator;
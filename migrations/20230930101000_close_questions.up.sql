@@ -0,0 +1,10 @@
+ALTER TABLE accounts
+ADD COLUMN is_moderator boolean NOT NULL DEFAULT false;
+
+ALTER TABLE questions
+ADD COLUMN close_reason VARCHAR(32),
+ADD COLUMN close_message TEXT,
+ADD COLUMN duplicate_of integer REFERENCES questions ON DELETE SET NULL,
+ADD COLUMN closed_by integer,
+ADD COLUMN closed_on TIMESTAMP,
+ADD CONSTRAINT questions_closed_check CHECK ((close_reason IS NULL) = (closed_on IS NULL));
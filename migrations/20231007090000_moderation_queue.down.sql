@@ -0,0 +1,8 @@
+DROP TABLE IF EXISTS moderation_actions;
+DROP TABLE IF EXISTS moderation_items;
+
+ALTER TABLE answers
+DROP COLUMN visibility;
+
+ALTER TABLE questions
+DROP COLUMN visibility;
@@ -0,0 +1,32 @@
+ALTER TABLE questions
+ADD COLUMN visibility VARCHAR(16) NOT NULL DEFAULT 'visible';
+
+ALTER TABLE answers
+ADD COLUMN visibility VARCHAR(16) NOT NULL DEFAULT 'visible';
+
+CREATE TABLE IF NOT EXISTS moderation_items (
+  id serial PRIMARY KEY,
+  question_id integer REFERENCES questions ON DELETE CASCADE,
+  answer_id integer REFERENCES answers ON DELETE CASCADE,
+  source VARCHAR(32) NOT NULL,
+  reason TEXT,
+  reported_by integer,
+  status VARCHAR(16) NOT NULL DEFAULT 'pending',
+  resolved_by integer,
+  resolved_on TIMESTAMP,
+  created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+  CHECK ((question_id IS NULL) <> (answer_id IS NULL))
+);
+
+CREATE INDEX IF NOT EXISTS moderation_items_status_idx ON moderation_items (status, created_on);
+
+CREATE TABLE IF NOT EXISTS moderation_actions (
+  id serial PRIMARY KEY,
+  item_id integer NOT NULL REFERENCES moderation_items ON DELETE CASCADE,
+  moderator_account_id integer NOT NULL,
+  action VARCHAR(16) NOT NULL,
+  note TEXT,
+  previous_title VARCHAR(255),
+  previous_content TEXT,
+  created_on TIMESTAMP NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS answers_content_hash_idx;
+DROP INDEX IF EXISTS questions_content_hash_idx;
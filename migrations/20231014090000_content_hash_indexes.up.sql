@@ -0,0 +1,3 @@
+-- Duplicate posts are looked up by the hash of their content
+CREATE INDEX IF NOT EXISTS questions_content_hash_idx ON questions (md5(content), created_on);
+CREATE INDEX IF NOT EXISTS answers_content_hash_idx ON answers (md5(content), created_on);
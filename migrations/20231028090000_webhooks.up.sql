@@ -0,0 +1,29 @@
+CREATE TABLE IF NOT EXISTS webhooks (
+  id serial PRIMARY KEY,
+  account_id integer NOT NULL,
+  url TEXT NOT NULL,
+  secret TEXT NOT NULL,
+  events TEXT[] NOT NULL,
+  created_on TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS webhook_deliveries (
+  id serial PRIMARY KEY,
+  webhook_id integer NOT NULL REFERENCES webhooks(id) ON DELETE CASCADE,
+  event VARCHAR(32) NOT NULL,
+  payload JSONB NOT NULL,
+  status VARCHAR(16) NOT NULL DEFAULT 'pending',
+  attempts integer NOT NULL DEFAULT 0,
+  last_status_code integer,
+  last_error TEXT,
+  -- NULL once delivered or dead-lettered
+  next_attempt_on TIMESTAMP DEFAULT NOW(),
+  created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+  delivered_on TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS webhooks_account_id_idx ON webhooks (account_id);
+CREATE INDEX IF NOT EXISTS webhook_deliveries_webhook_id_idx
+  ON webhook_deliveries (webhook_id, created_on);
+CREATE INDEX IF NOT EXISTS webhook_deliveries_pending_idx
+  ON webhook_deliveries (next_attempt_on) WHERE status = 'pending';
@@ -0,0 +1,20 @@
+CREATE TABLE IF NOT EXISTS jobs (
+  id bigserial PRIMARY KEY,
+  kind VARCHAR(64) NOT NULL,
+  payload JSONB NOT NULL,
+  status VARCHAR(16) NOT NULL DEFAULT 'pending',
+  attempts integer NOT NULL DEFAULT 0,
+  max_attempts integer NOT NULL,
+  run_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  -- Running jobs whose lock expired are claimed again
+  locked_until TIMESTAMP,
+  recurring_key VARCHAR(64),
+  last_error TEXT,
+  created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+  finished_on TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS jobs_due_idx ON jobs (run_at) WHERE status IN ('pending', 'running');
+-- At most one queued occurrence per recurring job, even with several instances
+CREATE UNIQUE INDEX IF NOT EXISTS jobs_recurring_key_idx ON jobs (recurring_key)
+  WHERE status IN ('pending', 'running');
@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS accounts_email_idx;
+
+ALTER TABLE accounts DROP CONSTRAINT accounts_pkey;
+ALTER TABLE accounts ADD PRIMARY KEY (email);
@@ -0,0 +1,6 @@
+-- Accounts are referenced by id, emails are unique regardless of case.
+-- Fails if two accounts share an email in different case, those have to be merged first.
+ALTER TABLE accounts DROP CONSTRAINT accounts_pkey;
+ALTER TABLE accounts ADD PRIMARY KEY (id);
+
+CREATE UNIQUE INDEX IF NOT EXISTS accounts_email_idx ON accounts (lower(email));
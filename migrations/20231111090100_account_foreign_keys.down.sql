@@ -0,0 +1,20 @@
+DROP INDEX IF EXISTS answers_corresponding_question_idx;
+DROP INDEX IF EXISTS answers_account_id_idx;
+DROP INDEX IF EXISTS questions_account_id_idx;
+
+ALTER TABLE answers
+DROP CONSTRAINT answers_account_id_fkey,
+DROP CONSTRAINT answers_corresponding_question_fkey,
+ADD CONSTRAINT answers_corresponding_question_fkey
+  FOREIGN KEY (corresponding_question) REFERENCES questions(id),
+ALTER COLUMN corresponding_question DROP NOT NULL;
+
+ALTER TABLE questions DROP CONSTRAINT questions_account_id_fkey;
+
+-- Restores the `account_id serial` defaults, continuing after the highest account id in use
+CREATE SEQUENCE IF NOT EXISTS questions_account_id_seq OWNED BY questions.account_id;
+CREATE SEQUENCE IF NOT EXISTS answers_account_id_seq OWNED BY answers.account_id;
+SELECT setval('questions_account_id_seq', COALESCE(MAX(account_id), 0) + 1, false) FROM questions;
+SELECT setval('answers_account_id_seq', COALESCE(MAX(account_id), 0) + 1, false) FROM answers;
+ALTER TABLE questions ALTER COLUMN account_id SET DEFAULT nextval('questions_account_id_seq');
+ALTER TABLE answers ALTER COLUMN account_id SET DEFAULT nextval('answers_account_id_seq');
@@ -0,0 +1,44 @@
+-- Fails if an answer has no question or a post references no account, posts are never
+-- dropped here. Those have to be reassigned, e.g. to a placeholder account, or deleted first.
+DO $$
+BEGIN
+  IF EXISTS (SELECT 1 FROM answers WHERE corresponding_question IS NULL) THEN
+    RAISE EXCEPTION 'Answers without a question exist, assign them to a question first';
+  END IF;
+  IF EXISTS (
+    SELECT 1 FROM questions
+    WHERE NOT EXISTS (SELECT 1 FROM accounts WHERE accounts.id = questions.account_id)
+  ) THEN
+    RAISE EXCEPTION 'Questions of unknown accounts exist, assign them to an account first';
+  END IF;
+  IF EXISTS (
+    SELECT 1 FROM answers
+    WHERE NOT EXISTS (SELECT 1 FROM accounts WHERE accounts.id = answers.account_id)
+  ) THEN
+    RAISE EXCEPTION 'Answers of unknown accounts exist, assign them to an account first';
+  END IF;
+END $$;
+
+-- `account_id serial` generated ids instead of referencing the author
+ALTER TABLE questions ALTER COLUMN account_id DROP DEFAULT;
+ALTER TABLE answers ALTER COLUMN account_id DROP DEFAULT;
+DROP SEQUENCE IF EXISTS questions_account_id_seq;
+DROP SEQUENCE IF EXISTS answers_account_id_seq;
+
+-- Answers go with their question
+ALTER TABLE answers
+ALTER COLUMN corresponding_question SET NOT NULL,
+DROP CONSTRAINT answers_corresponding_question_fkey,
+ADD CONSTRAINT answers_corresponding_question_fkey
+  FOREIGN KEY (corresponding_question) REFERENCES questions(id) ON DELETE CASCADE;
+
+ALTER TABLE questions
+ADD CONSTRAINT questions_account_id_fkey
+  FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE;
+ALTER TABLE answers
+ADD CONSTRAINT answers_account_id_fkey
+  FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE;
+
+CREATE INDEX IF NOT EXISTS questions_account_id_idx ON questions (account_id);
+CREATE INDEX IF NOT EXISTS answers_account_id_idx ON answers (account_id);
+CREATE INDEX IF NOT EXISTS answers_corresponding_question_idx ON answers (corresponding_question);
@@ -0,0 +1,8 @@
+DROP TRIGGER IF EXISTS answers_updated_on ON answers;
+DROP TRIGGER IF EXISTS questions_updated_on ON questions;
+DROP TRIGGER IF EXISTS accounts_updated_on ON accounts;
+DROP FUNCTION IF EXISTS set_updated_on();
+
+ALTER TABLE answers DROP COLUMN updated_on;
+ALTER TABLE questions DROP COLUMN updated_on;
+ALTER TABLE accounts DROP COLUMN updated_on;
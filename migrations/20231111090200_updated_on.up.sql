@@ -0,0 +1,31 @@
+ALTER TABLE accounts ADD COLUMN updated_on TIMESTAMP;
+ALTER TABLE questions ADD COLUMN updated_on TIMESTAMP;
+ALTER TABLE answers ADD COLUMN updated_on TIMESTAMP;
+
+UPDATE accounts SET updated_on = created_on;
+UPDATE questions SET updated_on = created_on;
+UPDATE answers SET updated_on = created_on;
+
+ALTER TABLE accounts ALTER COLUMN updated_on SET DEFAULT NOW(), ALTER COLUMN updated_on SET NOT NULL;
+ALTER TABLE questions ALTER COLUMN updated_on SET DEFAULT NOW(), ALTER COLUMN updated_on SET NOT NULL;
+ALTER TABLE answers ALTER COLUMN updated_on SET DEFAULT NOW(), ALTER COLUMN updated_on SET NOT NULL;
+
+-- Kept up to date on every update, whichever query changed the row
+CREATE OR REPLACE FUNCTION set_updated_on() RETURNS trigger AS $$
+BEGIN
+  NEW.updated_on = NOW();
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Accounts only change with the author shown on their questions, recomputing the reputation
+-- or changing the password must not change the timestamps of all their questions
+CREATE TRIGGER accounts_updated_on BEFORE UPDATE OF display_name, avatar_url ON accounts
+  FOR EACH ROW
+  WHEN (OLD.display_name IS DISTINCT FROM NEW.display_name
+    OR OLD.avatar_url IS DISTINCT FROM NEW.avatar_url)
+  EXECUTE FUNCTION set_updated_on();
+CREATE TRIGGER questions_updated_on BEFORE UPDATE ON questions
+  FOR EACH ROW EXECUTE FUNCTION set_updated_on();
+CREATE TRIGGER answers_updated_on BEFORE UPDATE ON answers
+  FOR EACH ROW EXECUTE FUNCTION set_updated_on();
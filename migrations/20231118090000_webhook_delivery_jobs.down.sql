@@ -0,0 +1,19 @@
+ALTER TABLE webhook_deliveries ADD COLUMN IF NOT EXISTS next_attempt_on TIMESTAMP DEFAULT NOW();
+UPDATE webhook_deliveries SET next_attempt_on = NULL WHERE status <> 'pending';
+CREATE INDEX IF NOT EXISTS webhook_deliveries_pending_idx
+  ON webhook_deliveries (next_attempt_on) WHERE status = 'pending';
+
+-- Reputation which was still to be recomputed is recomputed right away, pending deliveries
+-- are picked up by the webhook worker again
+UPDATE accounts SET reputation = COALESCE(
+  (SELECT SUM(points) FROM reputation_events WHERE reputation_events.account_id = accounts.id), 0)
+WHERE id IN (
+  SELECT jsonb_array_elements_text(payload->'account_ids')::integer FROM jobs
+  WHERE kind = 'recompute_reputation' AND status IN ('pending', 'running')
+);
+DELETE FROM jobs
+WHERE kind IN ('deliver_webhook', 'recompute_reputation') AND status IN ('pending', 'running');
+DROP INDEX IF EXISTS jobs_webhook_delivery_idx;
+
+UPDATE jobs SET max_attempts = GREATEST(attempts, 1) WHERE max_attempts IS NULL;
+ALTER TABLE jobs ALTER COLUMN max_attempts SET NOT NULL;
@@ -0,0 +1,16 @@
+-- Jobs queued by requests are given up after the attempts configured for their kind
+ALTER TABLE jobs ALTER COLUMN max_attempts DROP NOT NULL;
+
+-- Webhook deliveries are sent by the job runner, with at most one queued job per delivery
+CREATE UNIQUE INDEX IF NOT EXISTS jobs_webhook_delivery_idx
+  ON jobs (((payload->>'delivery_id')::integer))
+  WHERE kind = 'deliver_webhook' AND status IN ('pending', 'running');
+
+INSERT INTO jobs (kind, payload, run_at)
+SELECT 'deliver_webhook', jsonb_build_object('delivery_id', id), COALESCE(next_attempt_on, NOW())
+FROM webhook_deliveries
+WHERE status = 'pending';
+
+-- The next attempt is the run_at of the queued job
+DROP INDEX IF EXISTS webhook_deliveries_pending_idx;
+ALTER TABLE webhook_deliveries DROP COLUMN IF EXISTS next_attempt_on;
@@ -0,0 +1,7 @@
+DELETE FROM moderation_items WHERE comment_id IS NOT NULL;
+ALTER TABLE moderation_items
+DROP CONSTRAINT IF EXISTS moderation_items_check,
+DROP COLUMN IF EXISTS comment_id,
+ADD CONSTRAINT moderation_items_check CHECK ((question_id IS NULL) <> (answer_id IS NULL));
+
+ALTER TABLE comments DROP COLUMN IF EXISTS visibility;
@@ -0,0 +1,9 @@
+-- Comments are reviewed like questions and answers, e.g. when accepted unchecked while the
+-- profanity API was unavailable
+ALTER TABLE comments
+ADD COLUMN IF NOT EXISTS visibility VARCHAR(16) NOT NULL DEFAULT 'visible';
+
+ALTER TABLE moderation_items
+ADD COLUMN IF NOT EXISTS comment_id integer REFERENCES comments ON DELETE CASCADE,
+DROP CONSTRAINT IF EXISTS moderation_items_check,
+ADD CONSTRAINT moderation_items_check CHECK (num_nonnulls(question_id, answer_id, comment_id) = 1);
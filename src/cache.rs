@@ -0,0 +1,179 @@
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+struct Entries<K, V> {
+    values: HashMap<K, (Instant, V)>,
+    /// Keys in insertion order, so expired entries are at the front and the oldest entry is
+    /// evicted first
+    order: VecDeque<K>,
+}
+
+impl<K: Hash + Eq, V> Entries<K, V> {
+    fn remove_expired(&mut self, ttl: Duration) {
+        while let Some(oldest) = self.order.front() {
+            match self.values.get(oldest) {
+                Some((inserted, _)) if inserted.elapsed() < ttl => break,
+                _ => {
+                    if let Some(oldest) = self.order.pop_front() {
+                        self.values.remove(&oldest);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// In-process cache holding at most `capacity` entries for at most `ttl` each.
+/// A capacity of 0 disables the cache.
+pub struct TtlCache<K, V> {
+    capacity: usize,
+    ttl: Duration,
+    entries: Mutex<Entries<K, V>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> TtlCache<K, V> {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        TtlCache {
+            capacity,
+            ttl,
+            entries: Mutex::new(Entries {
+                values: HashMap::new(),
+                order: VecDeque::new(),
+            }),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.remove_expired(self.ttl);
+
+        match entries.values.get(key) {
+            Some((_, value)) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Some(value.clone())
+            }
+            _ => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    pub fn insert(&self, key: K, value: V) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.remove_expired(self.ttl);
+
+        // A re-inserted key starts over, it expires and is evicted last
+        if entries
+            .values
+            .insert(key.clone(), (Instant::now(), value))
+            .is_some()
+        {
+            if let Some(position) = entries.order.iter().position(|k| *k == key) {
+                entries.order.remove(position);
+            }
+        }
+        entries.order.push_back(key);
+
+        while entries.values.len() > self.capacity {
+            match entries.order.pop_front() {
+                Some(oldest) => {
+                    entries.values.remove(&oldest);
+                }
+                None => break,
+            }
+        }
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn len<K, V>(cache: &TtlCache<K, V>) -> (usize, usize) {
+        let entries = cache.entries.lock().unwrap();
+        (entries.values.len(), entries.order.len())
+    }
+
+    #[test]
+    fn returns_inserted_values_and_counts_lookups() {
+        let cache = TtlCache::new(10, Duration::from_secs(60));
+        cache.insert("a", 1);
+        assert_eq!(cache.get(&"a"), Some(1));
+        assert_eq!(cache.get(&"b"), None);
+        assert_eq!((cache.hits(), cache.misses()), (1, 1));
+    }
+
+    #[test]
+    fn capacity_of_zero_disables_the_cache() {
+        let cache = TtlCache::new(0, Duration::from_secs(60));
+        cache.insert("a", 1);
+        assert_eq!(cache.get(&"a"), None);
+        assert_eq!(len(&cache), (0, 0));
+    }
+
+    #[test]
+    fn evicts_the_oldest_entry_over_capacity() {
+        let cache = TtlCache::new(2, Duration::from_secs(60));
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        cache.insert("c", 3);
+        assert_eq!(cache.get(&"a"), None);
+        assert_eq!(cache.get(&"b"), Some(2));
+        assert_eq!(cache.get(&"c"), Some(3));
+    }
+
+    #[test]
+    fn reinserted_key_is_evicted_last() {
+        let cache = TtlCache::new(2, Duration::from_secs(60));
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        cache.insert("a", 3);
+        cache.insert("c", 4);
+        assert_eq!(cache.get(&"a"), Some(3));
+        assert_eq!(cache.get(&"b"), None);
+        assert_eq!(len(&cache), (2, 2));
+    }
+
+    #[test]
+    fn drops_expired_entries_on_lookup() {
+        let cache = TtlCache::new(10, Duration::from_millis(20));
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        std::thread::sleep(Duration::from_millis(30));
+        cache.insert("c", 3);
+
+        assert_eq!(cache.get(&"a"), None);
+        assert_eq!(cache.get(&"c"), Some(3));
+        assert_eq!(len(&cache), (1, 1));
+    }
+
+    #[test]
+    fn reinserted_key_expires_from_its_new_insertion() {
+        let cache = TtlCache::new(10, Duration::from_millis(40));
+        cache.insert("a", 1);
+        std::thread::sleep(Duration::from_millis(25));
+        cache.insert("a", 2);
+        std::thread::sleep(Duration::from_millis(25));
+        assert_eq!(cache.get(&"a"), Some(2));
+    }
+}
@@ -0,0 +1,139 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    /// Requests pass, counting consecutive failures
+    Closed { failures: u32 },
+    /// Requests are refused until the cooldown has passed
+    Open { since: Instant },
+    /// A single trial request is in flight to probe the upstream
+    HalfOpen { since: Instant },
+}
+
+/// Stops calling an upstream service after `failure_threshold` consecutive failures.
+/// After `cooldown` a single trial request is let through; its outcome decides whether the
+/// breaker closes again or stays open for another cooldown.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    state: Mutex<State>,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        CircuitBreaker {
+            failure_threshold,
+            cooldown,
+            state: Mutex::new(State::Closed { failures: 0 }),
+        }
+    }
+
+    /// Whether a request may be sent to the upstream right now
+    pub fn allow(&self) -> bool {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+
+        match *state {
+            State::Closed { .. } => true,
+            // A trial request which never reported back (e.g. its future was dropped) must not
+            // keep the breaker half open forever
+            State::Open { since } | State::HalfOpen { since }
+                if since.elapsed() >= self.cooldown =>
+            {
+                *state = State::HalfOpen {
+                    since: Instant::now(),
+                };
+                true
+            }
+            State::Open { .. } | State::HalfOpen { .. } => false,
+        }
+    }
+
+    /// Whether the upstream is considered healthy, without probing it
+    pub fn is_closed(&self) -> bool {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        matches!(*state, State::Closed { .. })
+    }
+
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        *state = State::Closed { failures: 0 };
+    }
+
+    pub fn record_failure(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+
+        *state = match *state {
+            State::Closed { failures } if failures + 1 < self.failure_threshold => State::Closed {
+                failures: failures + 1,
+            },
+            _ => {
+                tracing::event!(tracing::Level::WARN, "Circuit breaker opened");
+                State::Open {
+                    since: Instant::now(),
+                }
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opens_after_consecutive_failures() {
+        let breaker = CircuitBreaker::new(3, Duration::from_secs(60));
+        breaker.record_failure();
+        breaker.record_failure();
+        assert!(breaker.allow());
+        assert!(breaker.is_closed());
+
+        breaker.record_failure();
+        assert!(!breaker.allow());
+        assert!(!breaker.is_closed());
+    }
+
+    #[test]
+    fn success_resets_failures() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+        breaker.record_failure();
+        breaker.record_success();
+        breaker.record_failure();
+        assert!(breaker.allow());
+        assert!(breaker.is_closed());
+    }
+
+    #[test]
+    fn lets_a_single_trial_through_after_cooldown() {
+        let breaker = CircuitBreaker::new(1, Duration::from_millis(20));
+        breaker.record_failure();
+        assert!(!breaker.allow());
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(breaker.allow());
+        assert!(!breaker.allow());
+
+        // A failed trial opens the breaker for another cooldown
+        breaker.record_failure();
+        assert!(!breaker.allow());
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(breaker.allow());
+
+        breaker.record_success();
+        assert!(breaker.is_closed());
+        assert!(breaker.allow());
+    }
+
+    #[test]
+    fn trial_without_outcome_does_not_stay_half_open() {
+        let breaker = CircuitBreaker::new(1, Duration::from_millis(20));
+        breaker.record_failure();
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(breaker.allow());
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(breaker.allow());
+    }
+}
@@ -0,0 +1,189 @@
+use chrono::{DateTime, NaiveDateTime};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use warp::http::header::{self, HeaderValue};
+use warp::http::StatusCode;
+use warp::{Filter, Rejection, Reply};
+
+/// `If-None-Match` and `If-Modified-Since` of a `GET` request
+#[derive(Debug, Clone, Default)]
+pub struct Conditions {
+    if_none_match: Option<String>,
+    if_modified_since: Option<String>,
+}
+
+pub fn conditions() -> impl Filter<Extract = (Conditions,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("If-None-Match")
+        .and(warp::header::optional::<String>("If-Modified-Since"))
+        .map(|if_none_match, if_modified_since| Conditions {
+            if_none_match,
+            if_modified_since,
+        })
+}
+
+/// Strong entity tag of a response body, any change of the body changes the tag
+pub fn etag(body: &[u8]) -> String {
+    let digest = Sha256::digest(body);
+    format!("\"{}\"", hex::encode(&digest[..16]))
+}
+
+/// Entity tag of `value` as served by `Conditions::reply`
+pub fn etag_of(value: &impl Serialize) -> Result<String, serde_json::Error> {
+    serde_json::to_vec(value).map(|body| etag(&body))
+}
+
+/// Whether an `If-Match` header holds for the current `etag`. Weak tags never match, as they
+/// cannot tell whether the client has seen the exact same representation.
+pub fn if_match(header: &str, etag: &str) -> bool {
+    header
+        .split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag == etag)
+}
+
+/// Whether an `If-None-Match` header lists `etag`, weak tags match as well
+fn if_none_match(header: &str, etag: &str) -> bool {
+    header
+        .split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag)
+}
+
+/// Formats a timestamp of the database, which are kept in UTC, as HTTP date
+fn http_date(time: NaiveDateTime) -> String {
+    time.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+/// Adds `ETag` and `Last-Modified` to a response
+pub fn with_validators(
+    reply: impl Reply,
+    etag: &str,
+    last_modified: Option<NaiveDateTime>,
+) -> warp::reply::Response {
+    let mut response = reply.into_response();
+    let headers = response.headers_mut();
+    if let Ok(etag) = HeaderValue::from_str(etag) {
+        headers.insert(header::ETAG, etag);
+    }
+    if let Some(last_modified) = last_modified.map(http_date) {
+        if let Ok(last_modified) = HeaderValue::from_str(&last_modified) {
+            headers.insert(header::LAST_MODIFIED, last_modified);
+        }
+    }
+    response
+}
+
+impl Conditions {
+    /// Whether the client has the current representation already. `If-Modified-Since` is only
+    /// looked at without `If-None-Match`, as the entity tag is the more precise validator.
+    fn is_fresh(&self, etag: &str, last_modified: Option<NaiveDateTime>) -> bool {
+        if let Some(header) = &self.if_none_match {
+            return if_none_match(header, etag);
+        }
+        match (&self.if_modified_since, last_modified) {
+            // HTTP dates have no fractional seconds
+            (Some(since), Some(last_modified)) => DateTime::parse_from_rfc2822(since)
+                .map(|since| last_modified.timestamp() <= since.timestamp())
+                .unwrap_or(false),
+            _ => false,
+        }
+    }
+
+    /// Replies with `value` as JSON, or with an empty `304 Not Modified` if the client has it
+    /// already. Both carry `ETag` and, if known, `Last-Modified`.
+    pub fn reply(
+        &self,
+        value: &impl Serialize,
+        last_modified: Option<NaiveDateTime>,
+    ) -> warp::reply::Response {
+        let body = match serde_json::to_vec(value) {
+            Ok(body) => body,
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        };
+        let etag = etag(&body);
+        if self.is_fresh(&etag, last_modified) {
+            return with_validators(StatusCode::NOT_MODIFIED, &etag, last_modified);
+        }
+        with_validators(
+            warp::reply::with_header(body, header::CONTENT_TYPE, "application/json"),
+            &etag,
+            last_modified,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    const ETAG: &str = "\"abc\"";
+
+    fn time(hour: u32, min: u32, sec: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2023, 11, 11)
+            .and_then(|date| date.and_hms_milli_opt(hour, min, sec, 500))
+            .unwrap()
+    }
+
+    #[test]
+    fn if_match_needs_the_exact_strong_tag() {
+        assert!(if_match(ETAG, ETAG));
+        assert!(if_match("\"other\", \"abc\"", ETAG));
+        assert!(if_match("*", ETAG));
+        assert!(!if_match("W/\"abc\"", ETAG));
+        assert!(!if_match("\"other\"", ETAG));
+    }
+
+    #[test]
+    fn if_none_match_accepts_weak_tags() {
+        assert!(if_none_match(ETAG, ETAG));
+        assert!(if_none_match("W/\"abc\"", ETAG));
+        assert!(if_none_match("\"other\" ,W/\"abc\"", ETAG));
+        assert!(if_none_match("*", ETAG));
+        assert!(!if_none_match("\"other\"", ETAG));
+    }
+
+    #[test]
+    fn if_none_match_takes_precedence_over_if_modified_since() {
+        let conditions = Conditions {
+            if_none_match: Some("\"other\"".to_string()),
+            if_modified_since: Some(http_date(time(12, 0, 0))),
+        };
+        assert!(!conditions.is_fresh(ETAG, Some(time(11, 0, 0))));
+    }
+
+    #[test]
+    fn if_modified_since_ignores_fractional_seconds() {
+        let conditions = Conditions {
+            if_none_match: None,
+            if_modified_since: Some(http_date(time(12, 0, 0))),
+        };
+        assert!(conditions.is_fresh(ETAG, Some(time(12, 0, 0))));
+        assert!(!conditions.is_fresh(ETAG, Some(time(12, 0, 1))));
+        // Without `Last-Modified`, e.g. for listings, only the entity tag is compared
+        assert!(!conditions.is_fresh(ETAG, None));
+    }
+
+    #[test]
+    fn fresh_replies_have_no_body() {
+        let body = serde_json::to_vec(&vec![1, 2]).unwrap();
+        let conditions = Conditions {
+            if_none_match: Some(etag(&body)),
+            if_modified_since: None,
+        };
+        let response = conditions.reply(&vec![1, 2], None);
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[header::ETAG], etag(&body).as_str());
+        assert!(response.headers().get(header::LAST_MODIFIED).is_none());
+
+        let response = Conditions::default().reply(&vec![1, 2], Some(time(12, 0, 0)));
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::LAST_MODIFIED],
+            "Sat, 11 Nov 2023 12:00:00 GMT"
+        );
+    }
+}
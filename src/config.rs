@@ -0,0 +1,283 @@
+use serde::Deserialize;
+
+use crate::profanity::{FailureMode, ProfanityPolicy};
+use crate::rate_limit::{BucketLimit, RateLimitBackend};
+use crate::store::DatabaseSslMode;
+
+/// Application configuration read from `setup.toml`.
+/// Every value can be overridden by an environment variable, e.g.
+/// `APP_PROFANITY__ANSWER=reject` for `profanity.answer`.
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    pub profanity: ProfanityConfig,
+    #[serde(default)]
+    pub spam: SpamConfig,
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
+    #[serde(default)]
+    pub webhooks: WebhookConfig,
+    #[serde(default)]
+    pub jobs: JobConfig,
+    #[serde(default)]
+    pub shutdown: ShutdownConfig,
+    #[serde(default)]
+    pub health: HealthConfig,
+    #[serde(default)]
+    pub migrations: MigrationConfig,
+    #[serde(default)]
+    pub database: DatabaseConfig,
+}
+
+/// Profanity policy per content type and settings of the upstream API client
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct ProfanityConfig {
+    pub question_title: ProfanityPolicy,
+    pub question_content: ProfanityPolicy,
+    pub answer: ProfanityPolicy,
+    /// Timeout of a single upstream request, retries included separately
+    pub timeout_ms: u64,
+    pub max_retries: u32,
+    pub failure_mode: FailureMode,
+    /// Consecutive failures after which the upstream is no longer called
+    pub circuit_breaker_threshold: u32,
+    pub circuit_breaker_cooldown_secs: u64,
+    /// Maximum number of cached results, 0 disables the cache
+    pub cache_capacity: usize,
+    pub cache_ttl_secs: u64,
+}
+
+impl Default for ProfanityConfig {
+    fn default() -> Self {
+        ProfanityConfig {
+            question_title: ProfanityPolicy::default(),
+            question_content: ProfanityPolicy::default(),
+            answer: ProfanityPolicy::default(),
+            timeout_ms: 3000,
+            max_retries: 3,
+            failure_mode: FailureMode::default(),
+            circuit_breaker_threshold: 5,
+            circuit_breaker_cooldown_secs: 30,
+            cache_capacity: 1000,
+            cache_ttl_secs: 3600,
+        }
+    }
+}
+
+impl Config {
+    pub fn new() -> Result<Self, ::config::ConfigError> {
+        ::config::Config::builder()
+            .add_source(::config::File::with_name("setup").required(false))
+            .add_source(::config::Environment::with_prefix("APP").separator("__"))
+            .build()?
+            .try_deserialize::<Config>()
+    }
+}
+
+/// Posting rate limits and spam heuristics
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SpamConfig {
+    pub post_window_secs: u64,
+    /// Questions and answers per account within `post_window_secs`
+    pub posts_per_account: u32,
+    /// Questions and answers per IP address within `post_window_secs`
+    pub posts_per_ip: u32,
+    /// Limit for accounts younger than `new_account_age_hours`
+    pub new_account_posts: u32,
+    pub new_account_age_hours: i64,
+    pub registration_window_secs: u64,
+    pub registrations_per_ip: u32,
+    /// Posts identical to a post within this time are held for review
+    pub duplicate_window_secs: u64,
+    /// Posts with more links, or more links per word, are held for review
+    pub max_links: usize,
+    pub max_link_ratio: f64,
+}
+
+impl Default for SpamConfig {
+    fn default() -> Self {
+        SpamConfig {
+            post_window_secs: 600,
+            posts_per_account: 10,
+            posts_per_ip: 30,
+            new_account_posts: 3,
+            new_account_age_hours: 24,
+            registration_window_secs: 3600,
+            registrations_per_ip: 5,
+            duplicate_window_secs: 86400,
+            max_links: 3,
+            max_link_ratio: 0.2,
+        }
+    }
+}
+
+/// Rate limits of all requests per client and route group
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    pub backend: RateLimitBackend,
+    pub read: BucketLimit,
+    pub write: BucketLimit,
+    pub authentication: BucketLimit,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        RateLimitConfig {
+            enabled: true,
+            backend: RateLimitBackend::default(),
+            read: BucketLimit {
+                burst: 60,
+                per_minute: 120,
+            },
+            write: BucketLimit {
+                burst: 10,
+                per_minute: 30,
+            },
+            authentication: BucketLimit {
+                burst: 5,
+                per_minute: 10,
+            },
+        }
+    }
+}
+
+/// Delivery of webhooks by the job runner, which also decides when failed deliveries are
+/// attempted again
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct WebhookConfig {
+    /// Timeout of a single request, retries of transient errors included separately
+    pub timeout_ms: u64,
+    pub max_retries: u32,
+    /// Attempts after which a delivery is dead-lettered
+    pub max_attempts: i32,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        WebhookConfig {
+            timeout_ms: 5000,
+            max_retries: 2,
+            max_attempts: 8,
+        }
+    }
+}
+
+/// Background job runner and the retention of the recurring purge jobs
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct JobConfig {
+    /// How long the runner waits when no jobs are due
+    pub poll_interval_secs: u64,
+    /// Jobs claimed at once
+    pub batch_size: i64,
+    /// How long a claimed job is locked for the runner
+    pub lease_secs: u64,
+    /// Attempts after which a job is given up
+    pub max_attempts: i32,
+    pub backoff_base_secs: u64,
+    pub backoff_max_secs: u64,
+    pub finished_job_retention_hours: u64,
+    pub rate_limit_bucket_retention_hours: u64,
+}
+
+impl Default for JobConfig {
+    fn default() -> Self {
+        JobConfig {
+            poll_interval_secs: 5,
+            batch_size: 5,
+            lease_secs: 600,
+            max_attempts: 5,
+            backoff_base_secs: 10,
+            backoff_max_secs: 3600,
+            finished_job_retention_hours: 168,
+            rate_limit_bucket_retention_hours: 24,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct ShutdownConfig {
+    /// How long in-flight requests and background tasks may take to finish after SIGTERM
+    pub drain_timeout_secs: u64,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        ShutdownConfig {
+            drain_timeout_secs: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+pub struct HealthConfig {
+    /// Whether `/health/ready` fails while the profanity upstream is unavailable
+    pub check_profanity: bool,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct MigrationConfig {
+    /// Whether pending migrations are applied before the server starts
+    pub run_on_startup: bool,
+}
+
+impl Default for MigrationConfig {
+    fn default() -> Self {
+        MigrationConfig {
+            run_on_startup: true,
+        }
+    }
+}
+
+/// Connection pool and connection options of the Postgres database
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DatabaseConfig {
+    pub max_connections: u32,
+    /// Connections kept open while idle
+    pub min_connections: u32,
+    /// How long a query waits for a free connection
+    pub acquire_timeout_secs: u64,
+    /// Idle connections above `min_connections` are closed after this, 0 keeps them open
+    pub idle_timeout_secs: u64,
+    /// Queries running longer are cancelled by Postgres, 0 disables the timeout
+    pub statement_timeout_ms: u64,
+    /// Overrides the `sslmode` of `POSTGRES_DATABASE_URL` if set
+    pub ssl_mode: Option<DatabaseSslMode>,
+    pub application_name: String,
+    /// Retries of the initial connection, waiting twice as long after every failed attempt
+    pub connect_retries: u32,
+    pub connect_backoff_base_ms: u64,
+    pub connect_backoff_max_ms: u64,
+    /// How often replicas from `POSTGRES_REPLICA_URLS` are checked
+    pub replica_check_interval_secs: u64,
+    /// Replicas further behind the primary are not read from until they caught up
+    pub replica_max_lag_secs: u64,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        DatabaseConfig {
+            max_connections: 5,
+            min_connections: 0,
+            acquire_timeout_secs: 30,
+            idle_timeout_secs: 600,
+            statement_timeout_ms: 0,
+            ssl_mode: None,
+            application_name: env!("CARGO_PKG_NAME").to_string(),
+            connect_retries: 5,
+            connect_backoff_base_ms: 500,
+            connect_backoff_max_ms: 10000,
+            replica_check_interval_secs: 5,
+            replica_max_lag_secs: 30,
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use futures_util::{future, Stream, StreamExt};
+use sqlx::postgres::PgListener;
+use std::time::Duration;
+use tokio::sync::{broadcast, watch};
+use tokio::task::JoinHandle;
+use tokio_stream::wrappers::BroadcastStream;
+
+use crate::store::Store;
+use crate::types::feed::{FeedEvent, FeedFilter, FeedNotification, FEED_CHANNEL};
+
+/// Events kept for subscribers which fall behind, older events are skipped
+const CHANNEL_CAPACITY: usize = 256;
+
+/// Delay before listening again after the connection to Postgres failed
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Live feed of new and updated questions and answers.
+/// Store writes are sent to all instances with Postgres `NOTIFY`, every instance listens and
+/// broadcasts the posts to its own subscribers.
+#[derive(Debug, Clone)]
+pub struct Feed {
+    sender: broadcast::Sender<FeedEvent>,
+    stopped: watch::Receiver<bool>,
+}
+
+impl Feed {
+    /// Creates the feed and starts listening for notifications of the store until `stopped`
+    /// turns `true`. Returns the feed and the listening task.
+    pub fn new(store: Store, stopped: watch::Receiver<bool>) -> (Self, JoinHandle<()>) {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        let listener = tokio::spawn(listen(store, sender.clone(), stopped.clone()));
+
+        (Feed { sender, stopped }, listener)
+    }
+
+    /// Events matching `filter` from now on. The stream ends when the feed is stopped,
+    /// so open connections do not hold up the shutdown.
+    pub fn subscribe(&self, filter: FeedFilter) -> impl Stream<Item = FeedEvent> {
+        let mut stopped = self.stopped.clone();
+
+        BroadcastStream::new(self.sender.subscribe())
+            .filter_map(move |event| {
+                // Events missed by a lagging subscriber are skipped
+                future::ready(event.ok().filter(|event| filter.matches(event)))
+            })
+            .take_until(async move {
+                let _ = stopped.wait_for(|stopped| *stopped).await;
+            })
+    }
+}
+
+async fn listen(
+    store: Store,
+    sender: broadcast::Sender<FeedEvent>,
+    mut stopped: watch::Receiver<bool>,
+) {
+    while !*stopped.borrow() {
+        let mut listener = match PgListener::connect_with(&store.connection).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                tokio::time::sleep(RECONNECT_DELAY).await;
+                continue;
+            }
+        };
+        if let Err(e) = listener.listen(FEED_CHANNEL).await {
+            tracing::event!(tracing::Level::ERROR, "{:?}", e);
+            tokio::time::sleep(RECONNECT_DELAY).await;
+            continue;
+        }
+
+        // The listener reconnects by itself, notifications sent in the meantime are lost
+        loop {
+            let notification = tokio::select! {
+                notification = listener.recv() => notification,
+                _ = stopped.changed() => return,
+            };
+            let notification = match notification {
+                Ok(notification) => notification,
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    break;
+                }
+            };
+            let notification =
+                match serde_json::from_str::<FeedNotification>(notification.payload()) {
+                    Ok(notification) => notification,
+                    Err(e) => {
+                        tracing::event!(tracing::Level::WARN, "Invalid feed notification {}", e);
+                        continue;
+                    }
+                };
+
+            // Sending only fails without subscribers
+            if let Ok(Some(event)) = store.get_feed_event(notification).await {
+                let _ = sender.send(event);
+            }
+        }
+
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
@@ -0,0 +1,400 @@
+use chrono::{Duration as ChronoDuration, NaiveDateTime, NaiveTime, Utc};
+use futures_util::future::{self, BoxFuture, FutureExt};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+use crate::config::JobConfig;
+use crate::store::Store;
+use crate::types::account::AccountId;
+use crate::types::job::ClaimedJob;
+
+/// How often the runner makes sure every recurring job has an occurrence queued, e.g. after
+/// queueing the next one failed
+const RECURRING_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Work run outside of the request path. The job is stored as JSON, so it must contain
+/// everything the handler needs apart from `State`, which is shared by all jobs of a kind.
+pub trait Job: Serialize + DeserializeOwned + Send + 'static {
+    /// Value stored in the `kind` column of `jobs`
+    const KIND: &'static str;
+
+    type State: Clone + Send + Sync + 'static;
+
+    fn run(
+        self,
+        store: Store,
+        state: Self::State,
+    ) -> BoxFuture<'static, Result<(), handle_error::Error>>;
+}
+
+/// Queues `job` to run as soon as a runner is free. Run within a transaction, the job is
+/// only queued if the transaction is committed.
+pub async fn enqueue<J: Job>(store: &Store, job: J) -> Result<bool, handle_error::Error> {
+    let payload = serde_json::to_value(job).expect("Jobs serialize to JSON");
+    store
+        .enqueue_job(J::KIND, payload, Utc::now().naive_utc(), None, None)
+        .await
+}
+
+/// When a recurring job runs, in UTC
+#[derive(Debug, Clone, Copy)]
+pub enum Schedule {
+    Every(Duration),
+    /// Every day at the given time
+    Daily(NaiveTime),
+}
+
+impl Schedule {
+    /// Longest interval of `Schedule::Every`, longer ones are likely a unit mix-up
+    const MAX_INTERVAL: Duration = Duration::from_secs(366 * 24 * 60 * 60);
+
+    /// A zero interval would queue the next occurrence for the moment the previous one
+    /// finished, running the job without pause
+    fn is_valid(&self) -> bool {
+        match self {
+            Schedule::Every(interval) => !interval.is_zero() && *interval <= Self::MAX_INTERVAL,
+            Schedule::Daily(_) => true,
+        }
+    }
+
+    fn next_after(&self, now: NaiveDateTime) -> NaiveDateTime {
+        match self {
+            Schedule::Every(interval) => {
+                now + ChronoDuration::from_std(*interval)
+                    .expect("Intervals are checked by JobRunner::recurring")
+            }
+            Schedule::Daily(time) => {
+                let today = now.date().and_time(*time);
+                if today > now {
+                    today
+                } else {
+                    today + ChronoDuration::days(1)
+                }
+            }
+        }
+    }
+}
+
+type Handler =
+    Arc<dyn Fn(serde_json::Value, Store) -> BoxFuture<'static, Result<(), String>> + Send + Sync>;
+
+struct Recurring {
+    payload: serde_json::Value,
+    schedule: Schedule,
+}
+
+/// Runs jobs queued in Postgres. Several instances can run side by side, every job is
+/// claimed by one of them with `SELECT ... FOR UPDATE SKIP LOCKED`.
+pub struct JobRunner {
+    config: JobConfig,
+    store: Store,
+    handlers: HashMap<&'static str, Handler>,
+    /// Attempts of jobs queued without their own `max_attempts`, by kind
+    max_attempts: HashMap<&'static str, i32>,
+    recurring: HashMap<&'static str, Recurring>,
+}
+
+impl JobRunner {
+    pub fn new(config: JobConfig, store: Store) -> Self {
+        JobRunner {
+            config,
+            store,
+            handlers: HashMap::new(),
+            max_attempts: HashMap::new(),
+            recurring: HashMap::new(),
+        }
+    }
+
+    /// Runs jobs of kind `J::KIND` with `J::run`
+    pub fn register<J: Job<State = ()>>(self) -> Self {
+        self.register_with::<J>(())
+    }
+
+    /// Runs jobs of kind `J::KIND` with `J::run`, passing them `state`
+    pub fn register_with<J: Job>(mut self, state: J::State) -> Self {
+        let handler: Handler =
+            Arc::new(
+                move |payload, store| match serde_json::from_value::<J>(payload) {
+                    Ok(job) => job
+                        .run(store, state.clone())
+                        .map(|res| res.map_err(|e| e.to_string()))
+                        .boxed(),
+                    Err(e) => future::ready(Err(format!("Invalid payload: {}", e))).boxed(),
+                },
+            );
+        self.handlers.insert(J::KIND, handler);
+        self
+    }
+
+    /// Gives up jobs of kind `J::KIND` after `max_attempts` instead of `jobs.max_attempts`
+    pub fn max_attempts<J: Job>(mut self, max_attempts: i32) -> Self {
+        self.max_attempts.insert(J::KIND, max_attempts);
+        self
+    }
+
+    /// Runs `job` on `schedule`. The next occurrence is queued once the previous one finished.
+    /// Panics if `schedule` is an interval of zero or of more than a year.
+    pub fn recurring<J: Job<State = ()>>(mut self, job: J, schedule: Schedule) -> Self {
+        assert!(
+            schedule.is_valid(),
+            "Invalid schedule of recurring job {}: {:?}",
+            J::KIND,
+            schedule
+        );
+        let payload = serde_json::to_value(job).expect("Jobs serialize to JSON");
+        self.recurring
+            .insert(J::KIND, Recurring { payload, schedule });
+        self.register::<J>()
+    }
+
+    /// Starts running jobs until `stopped` turns `true`. Claimed jobs are finished first.
+    pub fn start(self, stopped: watch::Receiver<bool>) -> JoinHandle<()> {
+        tokio::spawn(self.run(stopped))
+    }
+
+    async fn run(self, mut stopped: watch::Receiver<bool>) {
+        let poll_interval = Duration::from_secs(self.config.poll_interval_secs);
+        let mut recurring_checked: Option<Instant> = None;
+        while !*stopped.borrow() {
+            // Nothing is queued for recurring jobs which have an occurrence pending or running
+            if recurring_checked.is_none_or(|at| at.elapsed() >= RECURRING_CHECK_INTERVAL) {
+                let now = Utc::now().naive_utc();
+                for (kind, recurring) in self.recurring.iter() {
+                    self.schedule(kind, recurring, now).await;
+                }
+                recurring_checked = Some(Instant::now());
+            }
+
+            // Failures are logged by the store
+            let jobs = self
+                .store
+                .claim_jobs(self.config.batch_size, self.config.lease_secs)
+                .await
+                .unwrap_or_default();
+
+            if jobs.is_empty() {
+                tokio::select! {
+                    _ = tokio::time::sleep(poll_interval) => {}
+                    _ = stopped.changed() => {}
+                }
+                continue;
+            }
+
+            // Claimed jobs are finished before stopping, they would be locked until the
+            // lease ran out otherwise
+            for job in jobs {
+                self.execute(job).await;
+            }
+        }
+    }
+
+    async fn execute(&self, job: ClaimedJob) {
+        let res = match self.handlers.get(job.kind.as_str()) {
+            Some(handler) => handler(job.payload, self.store.clone()).await,
+            None => Err(format!("No handler for job kind {}", job.kind)),
+        };
+
+        let max_attempts = job
+            .max_attempts
+            .or_else(|| self.max_attempts.get(job.kind.as_str()).copied())
+            .unwrap_or(self.config.max_attempts);
+        let retry_in_secs = match res {
+            Err(_) if job.attempts < max_attempts => Some(backoff_secs(&self.config, job.attempts)),
+            _ => None,
+        };
+        if let Err(e) = &res {
+            tracing::event!(
+                tracing::Level::WARN,
+                "Job {} ({}) failed in attempt {}: {}",
+                job.id.0,
+                job.kind,
+                job.attempts,
+                e
+            );
+        }
+
+        // Failures are logged by the store, the job runs again once its lease ran out
+        let _ = self
+            .store
+            .record_job_result(&job.id, res.err(), retry_in_secs)
+            .await;
+
+        // The next occurrence is queued once this one is done or given up
+        if let (Some(kind), None) = (job.recurring_key.as_deref(), retry_in_secs) {
+            if let Some((kind, recurring)) = self.recurring.get_key_value(kind) {
+                self.schedule(kind, recurring, Utc::now().naive_utc()).await;
+            }
+        }
+    }
+
+    async fn schedule(&self, kind: &str, recurring: &Recurring, now: NaiveDateTime) {
+        // Failures are logged by the store, the occurrence is queued by the next check of
+        // the recurring jobs instead
+        let _ = self
+            .store
+            .enqueue_job(
+                kind,
+                recurring.payload.clone(),
+                recurring.schedule.next_after(now),
+                None,
+                Some(kind),
+            )
+            .await;
+    }
+}
+
+/// Doubles the delay with every attempt, up to `backoff_max_secs`
+fn backoff_secs(config: &JobConfig, attempts: i32) -> u64 {
+    let exponent = attempts.saturating_sub(1).clamp(0, 31) as u32;
+    config
+        .backoff_base_secs
+        .saturating_mul(2u64.saturating_pow(exponent))
+        .min(config.backoff_max_secs)
+}
+
+/// Deletes finished jobs after `retention_secs`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PurgeFinishedJobs {
+    pub retention_secs: u64,
+}
+
+impl Job for PurgeFinishedJobs {
+    const KIND: &'static str = "purge_finished_jobs";
+
+    type State = ();
+
+    fn run(self, store: Store, _: ()) -> BoxFuture<'static, Result<(), handle_error::Error>> {
+        async move {
+            let purged = store.purge_finished_jobs(self.retention_secs).await?;
+            tracing::event!(tracing::Level::INFO, purged, "Purged finished jobs");
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+/// Deletes rate limit buckets unused for `retention_secs`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PurgeRateLimitBuckets {
+    pub retention_secs: u64,
+}
+
+impl Job for PurgeRateLimitBuckets {
+    const KIND: &'static str = "purge_rate_limit_buckets";
+
+    type State = ();
+
+    fn run(self, store: Store, _: ()) -> BoxFuture<'static, Result<(), handle_error::Error>> {
+        async move {
+            let purged = store.purge_rate_limit_buckets(self.retention_secs).await?;
+            tracing::event!(tracing::Level::INFO, purged, "Purged rate limit buckets");
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+/// Recomputes the cached reputation of accounts after the reputation ledger changed
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecomputeReputation {
+    pub account_ids: Vec<AccountId>,
+}
+
+impl Job for RecomputeReputation {
+    const KIND: &'static str = "recompute_reputation";
+
+    type State = ();
+
+    fn run(self, store: Store, _: ()) -> BoxFuture<'static, Result<(), handle_error::Error>> {
+        async move {
+            store.recompute_reputation(self.account_ids).await?;
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(hour: u32, min: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2023, 11, 20)
+            .unwrap()
+            .and_hms_opt(hour, min, 0)
+            .unwrap()
+    }
+
+    fn daily(hour: u32, min: u32) -> Schedule {
+        Schedule::Daily(NaiveTime::from_hms_opt(hour, min, 0).unwrap())
+    }
+
+    #[test]
+    fn daily_job_runs_later_today() {
+        assert_eq!(daily(3, 0).next_after(at(1, 30)), at(3, 0));
+    }
+
+    #[test]
+    fn daily_job_due_now_runs_tomorrow() {
+        assert_eq!(
+            daily(3, 0).next_after(at(3, 0)),
+            at(3, 0) + ChronoDuration::days(1)
+        );
+    }
+
+    #[test]
+    fn daily_job_past_today_runs_tomorrow() {
+        assert_eq!(
+            daily(3, 0).next_after(at(14, 15)),
+            at(3, 0) + ChronoDuration::days(1)
+        );
+    }
+
+    #[test]
+    fn interval_job_runs_after_interval() {
+        let schedule = Schedule::Every(Duration::from_secs(3600));
+        assert_eq!(
+            schedule.next_after(at(23, 30)),
+            at(23, 30) + ChronoDuration::hours(1)
+        );
+    }
+
+    #[test]
+    fn zero_and_huge_intervals_are_invalid() {
+        assert!(!Schedule::Every(Duration::ZERO).is_valid());
+        assert!(!Schedule::Every(Duration::MAX).is_valid());
+        assert!(!Schedule::Every(Schedule::MAX_INTERVAL + Duration::from_secs(1)).is_valid());
+        assert!(Schedule::Every(Schedule::MAX_INTERVAL).is_valid());
+        assert!(Schedule::Every(Duration::from_secs(1)).is_valid());
+        assert!(daily(0, 0).is_valid());
+    }
+
+    #[test]
+    fn backoff_doubles_with_every_attempt() {
+        let config = JobConfig::default();
+        assert_eq!(backoff_secs(&config, 1), 10);
+        assert_eq!(backoff_secs(&config, 2), 20);
+        assert_eq!(backoff_secs(&config, 3), 40);
+        assert_eq!(backoff_secs(&config, 4), 80);
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        let config = JobConfig::default();
+        assert_eq!(backoff_secs(&config, 10), 3600);
+        assert_eq!(backoff_secs(&config, i32::MAX), 3600);
+    }
+
+    #[test]
+    fn backoff_of_no_attempts_is_base() {
+        let config = JobConfig::default();
+        assert_eq!(backoff_secs(&config, 0), 10);
+        assert_eq!(backoff_secs(&config, -5), 10);
+    }
+}
@@ -1,20 +1,54 @@
 #![warn(clippy::all)]
 
+use chrono::NaiveTime;
 use dotenvy::dotenv;
 use handle_error::return_error;
 use std::env;
+use std::process;
+use std::time::Duration;
+use tokio::sync::watch;
 use tracing_subscriber::fmt::format::FmtSpan;
 use warp::{http::Method, Filter};
 
+mod cache;
+mod circuit_breaker;
+mod conditional;
+mod config;
+mod feed;
+mod jobs;
+mod metrics;
+mod migration;
 mod profanity;
+mod rate_limit;
 mod routes;
+mod shutdown;
+mod spam;
 mod store;
 mod types;
+mod webhook;
+
+const USAGE: &str = "Usage: rust-web-development [serve | migrate <run | revert | status>]";
 
 #[tokio::main]
 async fn main() {
     dotenv().ok();
 
+    let args: Vec<String> = env::args().skip(1).collect();
+    let migrate_command = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        [] | ["serve"] => None,
+        ["migrate", command] => match command.parse::<migration::MigrateCommand>() {
+            Ok(command) => Some(command),
+            Err(e) => {
+                eprintln!("{}\n{}", e, USAGE);
+                process::exit(2);
+            }
+        },
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+
     let postgres_database_url =
         env::var("POSTGRES_DATABASE_URL").expect("POSTGRES_DATABASE_URL must be set");
 
@@ -23,24 +57,138 @@ async fn main() {
         // https://docs.rs/tracing-subscriber/latest/tracing_subscriber/filter/struct.EnvFilter.html#examples-1
         .unwrap_or_else(|_| "handle_error=warn,rust_web_development=info,warp=warn".to_owned());
 
-    let store = store::Store::new(&postgres_database_url).await;
-    let store_filter = warp::any().map(move || store.clone());
+    let config = config::Config::new().expect("Cannot read configuration");
 
     tracing_subscriber::fmt()
         .with_env_filter(log_filter)
         .with_span_events(FmtSpan::CLOSE)
         .init();
 
+    // Comma separated, public listings are read from these if set
+    let postgres_replica_urls: Vec<String> = env::var("POSTGRES_REPLICA_URLS")
+        .map(|urls| {
+            urls.split(',')
+                .map(str::trim)
+                .filter(|url| !url.is_empty())
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let store = match store::Store::new(
+        &postgres_database_url,
+        &postgres_replica_urls,
+        &config.database,
+    )
+    .await
+    {
+        Ok(store) => store,
+        Err(e) => {
+            tracing::event!(tracing::Level::ERROR, "Cannot connect to database: {}", e);
+            process::exit(1);
+        }
+    };
+
+    if let Some(command) = migrate_command {
+        if let Err(e) = migration::execute(command, &store.connection).await {
+            eprintln!("Migration failed: {}", e);
+            process::exit(1);
+        }
+        return;
+    }
+
+    if config.migrations.run_on_startup {
+        if let Err(e) = migration::run(&store.connection).await {
+            tracing::event!(tracing::Level::ERROR, "Cannot apply migrations: {}", e);
+            process::exit(1);
+        }
+    }
+    let pools = store.clone();
+    let rate_limit = rate_limit::RateLimit::new(config.rate_limit, store.clone());
+
+    // Background tasks stop once this turns `true`
+    let (stop, stopped) = watch::channel(false);
+
+    let replica_monitor = store.monitor_replicas(
+        Duration::from_secs(config.database.replica_check_interval_secs),
+        config.database.replica_max_lag_secs,
+        stopped.clone(),
+    );
+
+    let (feed, feed_listener) = feed::Feed::new(store.clone(), stopped.clone());
+    let feed_filter = warp::any().map(move || feed.clone());
+
+    let job_runner = jobs::JobRunner::new(config.jobs, store.clone())
+        .register::<jobs::RecomputeReputation>()
+        .register_with::<webhook::DeliverWebhook>(webhook::WebhookClient::new(config.webhooks))
+        .max_attempts::<webhook::DeliverWebhook>(config.webhooks.max_attempts)
+        .recurring(
+            jobs::PurgeFinishedJobs {
+                retention_secs: config.jobs.finished_job_retention_hours * 3600,
+            },
+            jobs::Schedule::Daily(NaiveTime::from_hms_opt(3, 0, 0).expect("Valid time")),
+        )
+        .recurring(
+            jobs::PurgeRateLimitBuckets {
+                retention_secs: config.jobs.rate_limit_bucket_retention_hours * 3600,
+            },
+            jobs::Schedule::Every(Duration::from_secs(3600)),
+        )
+        .start(stopped.clone());
+    let store_filter = warp::any().map(move || store.clone());
+
+    let profanity = profanity::Profanity::new(config.profanity);
+    let profanity_filter = warp::any().map(move || profanity.clone());
+
+    let spam = spam::Spam::new(config.spam);
+    let spam_filter = warp::any().map(move || spam.clone());
+
     let cors = warp::cors()
         .allow_any_origin()
-        .allow_header("content-type")
+        .allow_headers([
+            "content-type",
+            "if-match",
+            "if-none-match",
+            "if-modified-since",
+        ])
+        .expose_headers(["etag", "last-modified"])
         .allow_methods(&[Method::GET, Method::POST, Method::PUT, Method::DELETE]);
 
+    let get_liveness = warp::get()
+        .and(warp::path("health"))
+        .and(warp::path("live"))
+        .and(warp::path::end())
+        .and_then(routes::health::get_liveness);
+
+    let check_profanity = config.health.check_profanity;
+    let get_readiness = warp::get()
+        .and(warp::path("health"))
+        .and(warp::path("ready"))
+        .and(warp::path::end())
+        .and(store_filter.clone())
+        .and(profanity_filter.clone())
+        .and(warp::any().map(move || check_profanity))
+        .and_then(routes::health::get_readiness);
+
+    let get_version = warp::get()
+        .and(warp::path("version"))
+        .and(warp::path::end())
+        .and(store_filter.clone())
+        .and_then(routes::health::get_version);
+
+    let get_metrics = warp::get()
+        .and(warp::path("metrics"))
+        .and(warp::path::end())
+        .and(store_filter.clone())
+        .and_then(routes::metrics::get_metrics);
+
     let get_questions = warp::get()
         .and(warp::path("questions"))
         .and(warp::path::end())
         .and(warp::query())
+        .and(routes::authentication::optional_auth())
         .and(store_filter.clone())
+        .and(conditional::conditions())
         .and_then(routes::question::get_questions)
         .with(warp::trace(|info| {
             tracing::info_span!(
@@ -53,18 +201,23 @@ async fn main() {
             )
         }));
 
-    // let get_question = warp::get()
-    //     .and(warp::path("questions"))
-    //     .and(warp::path::param::<i32>())
-    //     .and(warp::path::end())
-    //     .and(store_filter.clone())
-    //     .and_then(routes::question::get_question);
+    let get_question = warp::get()
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(routes::authentication::optional_auth())
+        .and(store_filter.clone())
+        .and(conditional::conditions())
+        .and_then(routes::question::get_question);
 
     let add_question = warp::post()
         .and(warp::path("questions"))
         .and(warp::path::end())
         .and(routes::authentication::auth())
         .and(store_filter.clone())
+        .and(profanity_filter.clone())
+        .and(spam_filter.clone())
+        .and(spam::client_ip())
         .and(warp::body::json())
         .and_then(routes::question::add_question);
 
@@ -74,6 +227,8 @@ async fn main() {
         .and(warp::path::end())
         .and(routes::authentication::auth())
         .and(store_filter.clone())
+        .and(profanity_filter.clone())
+        .and(warp::header::optional::<String>("If-Match"))
         .and(warp::body::json())
         .and_then(routes::question::update_question);
 
@@ -85,11 +240,33 @@ async fn main() {
         .and(store_filter.clone())
         .and_then(routes::question::delete_question);
 
+    let close_question = warp::post()
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("close"))
+        .and(warp::path::end())
+        .and(routes::authentication::auth())
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::question::close_question);
+
+    let reopen_question = warp::post()
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("reopen"))
+        .and(warp::path::end())
+        .and(routes::authentication::auth())
+        .and(store_filter.clone())
+        .and_then(routes::question::reopen_question);
+
     let add_answer = warp::post()
         .and(warp::path("answers"))
         .and(warp::path::end())
         .and(store_filter.clone())
         .and(routes::authentication::auth())
+        .and(profanity_filter.clone())
+        .and(spam_filter.clone())
+        .and(spam::client_ip())
         .and(warp::body::form())
         .and_then(routes::answer::add_answer);
 
@@ -97,6 +274,8 @@ async fn main() {
         .and(warp::path("registration"))
         .and(warp::path::end())
         .and(store_filter.clone())
+        .and(spam_filter.clone())
+        .and(spam::client_ip())
         .and(warp::body::json())
         .and_then(routes::authentication::register);
 
@@ -107,17 +286,361 @@ async fn main() {
         .and(warp::body::json())
         .and_then(routes::authentication::login);
 
-    let routes = get_questions
-        // .or(get_question)
+    let accept_answer = warp::post()
+        .and(warp::path("answers"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("accept"))
+        .and(warp::path::end())
+        .and(routes::authentication::auth())
+        .and(store_filter.clone())
+        .and_then(routes::answer::accept_answer);
+
+    let vote_question = warp::post()
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("votes"))
+        .and(warp::path::end())
+        .and(routes::authentication::auth())
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::vote::vote_question);
+
+    let vote_answer = warp::post()
+        .and(warp::path("answers"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("votes"))
+        .and(warp::path::end())
+        .and(routes::authentication::auth())
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::vote::vote_answer);
+
+    let get_comments = warp::get()
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("comments"))
+        .and(warp::path::end())
+        .and(store_filter.clone())
+        .and_then(routes::comment::get_comments);
+
+    let add_comment = warp::post()
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("comments"))
+        .and(warp::path::end())
+        .and(routes::authentication::auth())
+        .and(store_filter.clone())
+        .and(profanity_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::comment::add_comment);
+
+    let add_bookmark = warp::post()
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("bookmark"))
+        .and(warp::path::end())
+        .and(routes::authentication::auth())
+        .and(store_filter.clone())
+        .and_then(routes::bookmark::add_bookmark);
+
+    let delete_bookmark = warp::delete()
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("bookmark"))
+        .and(warp::path::end())
+        .and(routes::authentication::auth())
+        .and(store_filter.clone())
+        .and_then(routes::bookmark::delete_bookmark);
+
+    let get_bookmarks = warp::get()
+        .and(warp::path("account"))
+        .and(warp::path("bookmarks"))
+        .and(warp::path::end())
+        .and(warp::query())
+        .and(routes::authentication::auth())
+        .and(store_filter.clone())
+        .and_then(routes::bookmark::get_bookmarks);
+
+    let follow_question = warp::post()
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("follow"))
+        .and(warp::path::end())
+        .and(routes::authentication::auth())
+        .and(store_filter.clone())
+        .and_then(routes::follow::follow_question);
+
+    let unfollow_question = warp::delete()
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("follow"))
+        .and(warp::path::end())
+        .and(routes::authentication::auth())
+        .and(store_filter.clone())
+        .and_then(routes::follow::unfollow_question);
+
+    let follow_tag = warp::post()
+        .and(warp::path("tags"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("follow"))
+        .and(warp::path::end())
+        .and(routes::authentication::auth())
+        .and(store_filter.clone())
+        .and_then(routes::follow::follow_tag);
+
+    let unfollow_tag = warp::delete()
+        .and(warp::path("tags"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("follow"))
+        .and(warp::path::end())
+        .and(routes::authentication::auth())
+        .and(store_filter.clone())
+        .and_then(routes::follow::unfollow_tag);
+
+    let get_notifications = warp::get()
+        .and(warp::path("notifications"))
+        .and(warp::path::end())
+        .and(warp::query())
+        .and(routes::authentication::auth())
+        .and(store_filter.clone())
+        .and_then(routes::notification::get_notifications);
+
+    let mark_notification_read = warp::put()
+        .and(warp::path("notifications"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("read"))
+        .and(warp::path::end())
+        .and(routes::authentication::auth())
+        .and(store_filter.clone())
+        .and_then(routes::notification::mark_notification_read);
+
+    let mark_all_notifications_read = warp::put()
+        .and(warp::path("notifications"))
+        .and(warp::path("read"))
+        .and(warp::path::end())
+        .and(routes::authentication::auth())
+        .and(store_filter.clone())
+        .and_then(routes::notification::mark_all_notifications_read);
+
+    let flag_question = warp::post()
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("flag"))
+        .and(warp::path::end())
+        .and(routes::authentication::auth())
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::moderation::flag_question);
+
+    let flag_answer = warp::post()
+        .and(warp::path("answers"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("flag"))
+        .and(warp::path::end())
+        .and(routes::authentication::auth())
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::moderation::flag_answer);
+
+    let get_moderation_queue = warp::get()
+        .and(warp::path("moderation"))
+        .and(warp::path("queue"))
+        .and(warp::path::end())
+        .and(warp::query())
+        .and(routes::authentication::auth())
+        .and(store_filter.clone())
+        .and_then(routes::moderation::get_moderation_queue);
+
+    let get_moderation_actions = warp::get()
+        .and(warp::path("moderation"))
+        .and(warp::path("queue"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("actions"))
+        .and(warp::path::end())
+        .and(routes::authentication::auth())
+        .and(store_filter.clone())
+        .and_then(routes::moderation::get_moderation_actions);
+
+    let resolve_moderation_item = warp::post()
+        .and(warp::path("moderation"))
+        .and(warp::path("queue"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("resolve"))
+        .and(warp::path::end())
+        .and(routes::authentication::auth())
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::moderation::resolve_moderation_item);
+
+    let get_user = warp::get()
+        .and(warp::path("users"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(routes::authentication::optional_auth())
+        .and(store_filter.clone())
+        .and_then(routes::user::get_user);
+
+    let update_profile = warp::put()
+        .and(warp::path("account"))
+        .and(warp::path("profile"))
+        .and(warp::path::end())
+        .and(routes::authentication::auth())
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::user::update_profile);
+
+    let get_feed_events = warp::get()
+        .and(warp::path("feed"))
+        .and(warp::path::end())
+        .and(warp::query())
+        .and(feed_filter.clone())
+        .and_then(routes::feed::get_feed_events);
+
+    let get_feed_socket = warp::get()
+        .and(warp::path("feed"))
+        .and(warp::path("ws"))
+        .and(warp::path::end())
+        .and(warp::query())
+        .and(feed_filter.clone())
+        .and(warp::ws())
+        .and_then(routes::feed::get_feed_socket);
+
+    let add_webhook = warp::post()
+        .and(warp::path("webhooks"))
+        .and(warp::path::end())
+        .and(routes::authentication::auth())
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::webhook::add_webhook);
+
+    let get_webhooks = warp::get()
+        .and(warp::path("webhooks"))
+        .and(warp::path::end())
+        .and(routes::authentication::auth())
+        .and(store_filter.clone())
+        .and_then(routes::webhook::get_webhooks);
+
+    let delete_webhook = warp::delete()
+        .and(warp::path("webhooks"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(routes::authentication::auth())
+        .and(store_filter.clone())
+        .and_then(routes::webhook::delete_webhook);
+
+    let get_webhook_deliveries = warp::get()
+        .and(warp::path("webhooks"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("deliveries"))
+        .and(warp::path::end())
+        .and(warp::query())
+        .and(routes::authentication::auth())
+        .and(store_filter.clone())
+        .and_then(routes::webhook::get_webhook_deliveries);
+
+    let retry_webhook_delivery = warp::post()
+        .and(warp::path("webhooks"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("deliveries"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("retry"))
+        .and(warp::path::end())
+        .and(routes::authentication::auth())
+        .and(store_filter.clone())
+        .and_then(routes::webhook::retry_webhook_delivery);
+
+    // Boxed per group, a single chain of all routes nests the filter futures deep enough to
+    // overflow the stack in debug builds
+    let question_routes = get_questions
+        .or(get_question)
         .or(add_question)
         .or(update_question)
         .or(delete_question)
+        .or(close_question)
+        .or(reopen_question)
         .or(add_answer)
-        .or(registration)
+        .or(accept_answer)
+        .or(vote_question)
+        .or(vote_answer)
+        .or(get_comments)
+        .or(add_comment)
+        .boxed();
+
+    let account_routes = registration
         .or(login)
+        .or(add_bookmark)
+        .or(delete_bookmark)
+        .or(get_bookmarks)
+        .or(follow_question)
+        .or(unfollow_question)
+        .or(follow_tag)
+        .or(unfollow_tag)
+        .or(get_notifications)
+        .or(mark_notification_read)
+        .or(mark_all_notifications_read)
+        .or(get_user)
+        .or(update_profile)
+        .boxed();
+
+    let moderation_routes = flag_question
+        .or(flag_answer)
+        .or(get_moderation_queue)
+        .or(get_moderation_actions)
+        .or(resolve_moderation_item)
+        .boxed();
+
+    let integration_routes = get_feed_events
+        .or(get_feed_socket)
+        .or(add_webhook)
+        .or(get_webhooks)
+        .or(delete_webhook)
+        .or(get_webhook_deliveries)
+        .or(retry_webhook_delivery)
+        .boxed();
+
+    let routes = question_routes
+        .or(account_routes)
+        .or(moderation_routes)
+        .or(integration_routes);
+
+    let routes = rate_limit::rate_limit(rate_limit)
+        .and(routes)
+        .map(rate_limit::RateLimitStatus::apply)
         .with(cors)
-        .with(warp::trace::request())
-        .recover(return_error);
+        .with(warp::trace::request());
+
+    // Probes of the orchestrator and the metrics scraper are neither rate limited nor logged
+    let routes = get_liveness
+        .or(get_readiness)
+        .or(get_version)
+        .or(get_metrics)
+        .or(routes)
+        .recover(return_error)
+        .with(warp::log::custom(metrics::record_request));
+
+    let mut server_stopped = stopped.clone();
+    let (_, server) =
+        warp::serve(routes).bind_with_graceful_shutdown(([127, 0, 0, 1], 3030), async move {
+            let _ = server_stopped.wait_for(|stopped| *stopped).await;
+        });
+    let server = tokio::spawn(server);
+
+    shutdown::signal().await;
+    tracing::event!(tracing::Level::INFO, "Shutting down");
+    let _ = stop.send(true);
+
+    // In-flight requests and background tasks get until the deadline to finish
+    let drained = tokio::time::timeout(
+        Duration::from_secs(config.shutdown.drain_timeout_secs),
+        futures_util::future::join_all([server, feed_listener, job_runner, replica_monitor]),
+    )
+    .await;
+    if drained.is_err() {
+        tracing::event!(
+            tracing::Level::WARN,
+            "Shutdown deadline passed, dropping remaining connections and tasks"
+        );
+    }
 
-    warp::serve(routes).run(([127, 0, 0, 1], 3030)).await;
+    pools.close().await;
 }
@@ -0,0 +1,220 @@
+use once_cell::sync::Lazy;
+use prometheus::core::Collector;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramTimer, HistogramVec, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+use sqlx::PgPool;
+use std::time::Duration;
+use warp::http::StatusCode;
+
+static REGISTRY: Lazy<Registry> = Lazy::new(Registry::new);
+
+static HTTP_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register(
+        IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests by route and status"),
+            &["route", "method", "status"],
+        )
+        .expect("Valid metric"),
+    )
+});
+
+static HTTP_REQUEST_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    register(
+        HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "HTTP request latency by route and status",
+            ),
+            &["route", "method", "status"],
+        )
+        .expect("Valid metric"),
+    )
+});
+
+static STORE_QUERY_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    register(
+        HistogramVec::new(
+            HistogramOpts::new(
+                "store_query_duration_seconds",
+                "Duration of Store methods, including waiting for a connection",
+            ),
+            &["method"],
+        )
+        .expect("Valid metric"),
+    )
+});
+
+static DB_POOL_CONNECTIONS: Lazy<IntGauge> = Lazy::new(|| {
+    register(
+        IntGauge::new(
+            "db_pool_connections",
+            "Open Postgres connections of the pool",
+        )
+        .expect("Valid metric"),
+    )
+});
+
+static DB_POOL_IDLE_CONNECTIONS: Lazy<IntGauge> = Lazy::new(|| {
+    register(
+        IntGauge::new(
+            "db_pool_idle_connections",
+            "Idle Postgres connections of the pool",
+        )
+        .expect("Valid metric"),
+    )
+});
+
+static PROFANITY_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register(
+        IntCounterVec::new(
+            Opts::new(
+                "profanity_requests_total",
+                "Requests to the bad words API by outcome",
+            ),
+            &["outcome"],
+        )
+        .expect("Valid metric"),
+    )
+});
+
+static PROFANITY_REQUEST_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    register(
+        HistogramVec::new(
+            HistogramOpts::new(
+                "profanity_request_duration_seconds",
+                "Latency of the bad words API including retries",
+            ),
+            &["outcome"],
+        )
+        .expect("Valid metric"),
+    )
+});
+
+static PROFANITY_CACHE_LOOKUPS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register(
+        IntCounterVec::new(
+            Opts::new(
+                "profanity_cache_lookups_total",
+                "Lookups in the profanity result cache by result",
+            ),
+            &["result"],
+        )
+        .expect("Valid metric"),
+    )
+});
+
+fn register<C: Collector + Clone + 'static>(collector: C) -> C {
+    REGISTRY
+        .register(Box::new(collector.clone()))
+        .expect("Metric registered once");
+    collector
+}
+
+/// Records a finished HTTP request, used with `warp::log::custom`
+pub fn record_request(info: warp::log::Info) {
+    let route = route_label(info.path(), info.status());
+    let status = info.status().as_u16().to_string();
+    let labels = [route.as_str(), info.method().as_str(), status.as_str()];
+
+    HTTP_REQUESTS.with_label_values(&labels).inc();
+    HTTP_REQUEST_DURATION
+        .with_label_values(&labels)
+        .observe(info.elapsed().as_secs_f64());
+}
+
+/// Replaces ids, tags and other values in `path` by placeholders, so that every route gets
+/// a single label, e.g. `/questions/{id}/answers`. Requests which no route matched share the
+/// label `unmatched`, any path would become a label of its own otherwise.
+fn route_label(path: &str, status: StatusCode) -> String {
+    if status == StatusCode::NOT_FOUND || status == StatusCode::METHOD_NOT_ALLOWED {
+        return "unmatched".to_string();
+    }
+
+    let mut previous = "";
+    let segments: Vec<&str> = path
+        .trim_matches('/')
+        .split('/')
+        .map(|segment| {
+            let label = if segment.parse::<i64>().is_ok() {
+                "{id}"
+            } else if previous == "tags" {
+                "{tag}"
+            } else if segment.len() > 32
+                || !segment.chars().all(|c| c.is_ascii_lowercase() || c == '_')
+            {
+                "{param}"
+            } else {
+                segment
+            };
+            previous = segment;
+            label
+        })
+        .collect();
+
+    format!("/{}", segments.join("/"))
+}
+
+/// Observes the duration of a `Store` method until the returned timer is dropped
+pub fn store_query(method: &str) -> HistogramTimer {
+    STORE_QUERY_DURATION
+        .with_label_values(&[method])
+        .start_timer()
+}
+
+/// Records a request to the bad words API. `outcome` is e.g. `success` or `server_error`.
+pub fn profanity_request(outcome: &str, duration: Duration) {
+    PROFANITY_REQUESTS.with_label_values(&[outcome]).inc();
+    PROFANITY_REQUEST_DURATION
+        .with_label_values(&[outcome])
+        .observe(duration.as_secs_f64());
+}
+
+pub fn profanity_cache_lookup(hit: bool) {
+    let result = if hit { "hit" } else { "miss" };
+    PROFANITY_CACHE_LOOKUPS.with_label_values(&[result]).inc();
+}
+
+/// Renders all metrics in the Prometheus text format
+pub fn render(pool: &PgPool) -> String {
+    DB_POOL_CONNECTIONS.set(pool.size() as i64);
+    DB_POOL_IDLE_CONNECTIONS.set(pool.num_idle() as i64);
+
+    let mut buffer = Vec::new();
+    if let Err(e) = TextEncoder::new().encode(&REGISTRY.gather(), &mut buffer) {
+        tracing::event!(tracing::Level::ERROR, "{:?}", e);
+    }
+    String::from_utf8(buffer).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_are_replaced_by_placeholders() {
+        assert_eq!(
+            route_label("/questions/12/answers", StatusCode::OK),
+            "/questions/{id}/answers"
+        );
+        assert_eq!(route_label("/tags/rust", StatusCode::OK), "/tags/{tag}");
+        assert_eq!(
+            route_label("/webhooks/3/deliveries/7/retry", StatusCode::ACCEPTED),
+            "/webhooks/{id}/deliveries/{id}/retry"
+        );
+    }
+
+    #[test]
+    fn unmatched_paths_share_a_label() {
+        assert_eq!(route_label("/wp_admin", StatusCode::NOT_FOUND), "unmatched");
+        assert_eq!(
+            route_label("/questions/12/nonsense", StatusCode::NOT_FOUND),
+            "unmatched"
+        );
+        assert_eq!(
+            route_label("/health_check", StatusCode::METHOD_NOT_ALLOWED),
+            "unmatched"
+        );
+    }
+}
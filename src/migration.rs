@@ -0,0 +1,88 @@
+use sqlx::migrate::{Migrate, MigrateError, MigrationType, Migrator};
+use sqlx::PgPool;
+use std::str::FromStr;
+
+/// Migrations in `migrations/`, embedded into the binary
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// Subcommands of `rust-web-development migrate`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrateCommand {
+    /// Applies all pending migrations
+    Run,
+    /// Reverts the latest applied migration
+    Revert,
+    /// Lists all migrations and whether they are applied
+    Status,
+}
+
+impl FromStr for MigrateCommand {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "run" => Ok(MigrateCommand::Run),
+            "revert" => Ok(MigrateCommand::Revert),
+            "status" => Ok(MigrateCommand::Status),
+            _ => Err(format!("Unknown migrate command {}", s)),
+        }
+    }
+}
+
+pub async fn run(pool: &PgPool) -> Result<(), MigrateError> {
+    MIGRATOR.run(pool).await
+}
+
+pub async fn execute(command: MigrateCommand, pool: &PgPool) -> Result<(), MigrateError> {
+    match command {
+        MigrateCommand::Run => {
+            run(pool).await?;
+            println!("Applied all pending migrations");
+        }
+        MigrateCommand::Revert => {
+            let mut applied = applied_versions(pool).await?;
+            let Some(latest) = applied.pop() else {
+                println!("No migrations to revert");
+                return Ok(());
+            };
+            // Reverts every migration newer than the target, i.e. just the latest one
+            MIGRATOR.undo(pool, applied.pop().unwrap_or(0)).await?;
+            println!("Reverted migration {}", latest);
+        }
+        MigrateCommand::Status => {
+            let applied = applied_versions(pool).await?;
+            for migration in MIGRATOR.iter() {
+                if migration.migration_type == MigrationType::ReversibleDown {
+                    continue;
+                }
+                let status = if applied.contains(&migration.version) {
+                    "applied"
+                } else {
+                    "pending"
+                };
+                println!(
+                    "{} {:<8} {}",
+                    migration.version, status, migration.description
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Versions of the applied migrations, oldest first
+async fn applied_versions(pool: &PgPool) -> Result<Vec<i64>, MigrateError> {
+    let mut connection = pool.acquire().await?;
+    connection.ensure_migrations_table().await?;
+
+    let mut versions: Vec<i64> = connection
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|migration| migration.version)
+        .collect();
+    versions.sort_unstable();
+
+    Ok(versions)
+}
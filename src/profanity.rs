@@ -1,8 +1,16 @@
 use reqwest::Client;
-use reqwest_middleware::ClientBuilder;
+use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
 use reqwest_retry::{policies::ExponentialBackoff, RetryTransientMiddleware};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::env;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::cache::TtlCache;
+use crate::circuit_breaker::CircuitBreaker;
+use crate::config::ProfanityConfig;
+use crate::metrics;
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct APIResponse {
@@ -15,10 +23,17 @@ struct BadWord {
     word: String,
     deviations: i64,
     info: i64,
+    /// Character offset of the bad word in the content
+    #[serde(default)]
+    start: Option<usize>,
     #[serde(rename = "replacedLen")]
     replaced_len: i64,
 }
 
+/// Separates contents checked in a single upstream request. Censoring only replaces letters of
+/// bad words, so the separator comes back unchanged.
+const BATCH_SEPARATOR: &str = "\n\n~~~~~~~~\n\n";
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 struct BadWordsResponse {
     content: String,
@@ -27,41 +42,572 @@ struct BadWordsResponse {
     censored_content: String,
 }
 
-pub async fn check_profanity(content: String) -> Result<String, handle_error::Error> {
-    let retry_policy = ExponentialBackoff::builder().build_with_max_retries(3);
-    let client = ClientBuilder::new(Client::new())
-        .with(RetryTransientMiddleware::new_with_policy(retry_policy))
-        .build();
-    let res = client
-        .post(env::var("BAD_WORDS_API_ENDPOINT").expect("BAD_WORDS_API_ENDPOINT not set"))
-        .header(
-            "apiKey",
-            env::var("BAD_WORDS_API_KEY").expect("BAD_WORDS_API_KEY not set"),
-        )
-        .body(content)
-        .send()
-        .await
-        .map_err(handle_error::Error::ReqwestMiddlewareAPIError)?;
-
-    if !res.status().is_success() {
-        if res.status().is_client_error() {
-            let err = transform_error(res).await;
-            return Err(handle_error::Error::ClientError(err));
+/// Outcome of checking a text for profanity
+#[derive(Debug, Clone)]
+pub struct ProfanityCheck {
+    /// Content as submitted
+    pub content: String,
+    /// Content with all bad words replaced
+    pub censored_content: String,
+    pub bad_words_total: i64,
+    /// Bad words as they appear in the content
+    pub bad_words: Vec<String>,
+    /// Accepted without a check while the upstream was unavailable
+    pub unchecked: bool,
+}
+
+/// What to do with content in which bad words were found
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProfanityPolicy {
+    /// Store the censored content
+    #[default]
+    Censor,
+    /// Refuse the content
+    Reject,
+    /// Store the content as submitted, but hide it until a moderator reviewed it
+    Hold,
+}
+
+impl ProfanityPolicy {
+    /// Returns the content to store, or `Error::ProfanityRejected` listing the bad words
+    pub fn apply(&self, check: &ProfanityCheck) -> Result<String, handle_error::Error> {
+        match self {
+            ProfanityPolicy::Censor => Ok(check.censored_content.clone()),
+            ProfanityPolicy::Reject if check.bad_words_total > 0 => Err(
+                handle_error::Error::ProfanityRejected(check.bad_words.clone()),
+            ),
+            ProfanityPolicy::Reject | ProfanityPolicy::Hold => Ok(check.content.clone()),
+        }
+    }
+
+    /// Whether the content has to stay hidden until a moderator reviewed it
+    pub fn holds(&self, check: &ProfanityCheck) -> bool {
+        *self == ProfanityPolicy::Hold && check.bad_words_total > 0
+    }
+}
+
+impl ProfanityCheck {
+    /// Result used when the upstream is unavailable and the service fails open
+    fn unchecked(content: String) -> Self {
+        ProfanityCheck {
+            censored_content: content.clone(),
+            content,
+            bad_words_total: 0,
+            bad_words: vec![],
+            unchecked: true,
+        }
+    }
+
+    /// Why a moderator should review content with these checks, if at all
+    pub fn review_reason(checks: &[&ProfanityCheck]) -> Option<String> {
+        let bad_words_total: i64 = checks.iter().map(|check| check.bad_words_total).sum();
+        if bad_words_total > 0 {
+            Some(format!("{} bad words found", bad_words_total))
+        } else if checks.iter().any(|check| check.unchecked) {
+            Some("Not checked for bad words, the profanity service was unavailable".to_string())
         } else {
-            let err = transform_error(res).await;
-            return Err(handle_error::Error::ServerError(err));
+            None
+        }
+    }
+}
+
+/// How to handle content while the profanity upstream is unavailable
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureMode {
+    /// Accept the content unchecked, questions, answers and comments are queued for review
+    Open,
+    /// Refuse the content with `503 Service Unavailable`
+    #[default]
+    Closed,
+}
+
+/// Client of the APILayer bad words API, shared by all requests.
+/// Cloning is cheap, all clones share the connection pool and the circuit breaker.
+#[derive(Clone)]
+pub struct Profanity {
+    pub config: ProfanityConfig,
+    client: ClientWithMiddleware,
+    endpoint: String,
+    api_key: String,
+    circuit_breaker: Arc<CircuitBreaker>,
+    /// Results of earlier checks keyed by the SHA-256 hash of the content
+    cache: Arc<TtlCache<[u8; 32], ProfanityCheck>>,
+}
+
+impl Profanity {
+    pub fn new(config: ProfanityConfig) -> Self {
+        let client = Client::builder()
+            .timeout(Duration::from_millis(config.timeout_ms))
+            .build()
+            .expect("Cannot build profanity HTTP client");
+        let retry_policy = ExponentialBackoff::builder().build_with_max_retries(config.max_retries);
+        let client = ClientBuilder::new(client)
+            .with(RetryTransientMiddleware::new_with_policy(retry_policy))
+            .build();
+
+        Profanity {
+            config,
+            client,
+            endpoint: env::var("BAD_WORDS_API_ENDPOINT").expect("BAD_WORDS_API_ENDPOINT not set"),
+            api_key: env::var("BAD_WORDS_API_KEY").expect("BAD_WORDS_API_KEY not set"),
+            circuit_breaker: Arc::new(CircuitBreaker::new(
+                config.circuit_breaker_threshold,
+                Duration::from_secs(config.circuit_breaker_cooldown_secs),
+            )),
+            cache: Arc::new(TtlCache::new(
+                config.cache_capacity,
+                Duration::from_secs(config.cache_ttl_secs),
+            )),
         }
     }
 
-    match res.json::<BadWordsResponse>().await {
-        Ok(res) => Ok(res.censored_content),
-        Err(e) => Err(handle_error::Error::ReqwestAPIError(e)),
+    /// Whether the upstream answered recently, i.e. the circuit breaker is closed
+    pub fn is_available(&self) -> bool {
+        self.circuit_breaker.is_closed()
+    }
+
+    /// Checks `content` for bad words. While the upstream is unavailable the content is
+    /// accepted unchecked or refused, depending on the configured `FailureMode`.
+    pub async fn check(&self, content: String) -> Result<ProfanityCheck, handle_error::Error> {
+        let [check] = self.check_all([content]).await?;
+        Ok(check)
+    }
+
+    /// Checks several contents, e.g. title and content of a question, with a single upstream
+    /// request. Results are returned in the order of `contents`.
+    pub async fn check_all<const N: usize>(
+        &self,
+        contents: [String; N],
+    ) -> Result<[ProfanityCheck; N], handle_error::Error> {
+        let keys: Vec<[u8; 32]> = contents
+            .iter()
+            .map(|content| Sha256::digest(content.as_bytes()).into())
+            .collect();
+        let mut checks: Vec<Option<ProfanityCheck>> = keys
+            .iter()
+            .map(|key| {
+                let check = self.cache.get(key);
+                metrics::profanity_cache_lookup(check.is_some());
+                check
+            })
+            .collect();
+        tracing::event!(
+            tracing::Level::DEBUG,
+            hits = self.cache.hits(),
+            misses = self.cache.misses(),
+            "Profanity cache lookup"
+        );
+
+        let missing: Vec<usize> = (0..N).filter(|&i| checks[i].is_none()).collect();
+        if !missing.is_empty() {
+            let missing_contents: Vec<String> =
+                missing.iter().map(|&i| contents[i].clone()).collect();
+            let results = self.check_upstream(missing_contents).await?;
+
+            for (i, check) in missing.into_iter().zip(results) {
+                checks[i] = Some(check);
+            }
+        }
+
+        let mut checks = checks.into_iter().flatten();
+        Ok(std::array::from_fn(|_| {
+            checks.next().expect("One profanity check per content")
+        }))
+    }
+
+    async fn check_upstream(
+        &self,
+        contents: Vec<String>,
+    ) -> Result<Vec<ProfanityCheck>, handle_error::Error> {
+        if !self.circuit_breaker.allow() {
+            tracing::event!(tracing::Level::WARN, "Profanity circuit breaker is open");
+            return self.unavailable(contents);
+        }
+
+        match self.request_batch(&contents).await {
+            Ok(checks) => {
+                self.circuit_breaker.record_success();
+                // Unchecked results while failing open are never cached
+                for check in checks.iter() {
+                    self.cache.insert(
+                        Sha256::digest(check.content.as_bytes()).into(),
+                        check.clone(),
+                    );
+                }
+                Ok(checks)
+            }
+            // Transport errors, timeouts, server errors, rate limiting and a rejected API key mean
+            // the upstream is unavailable, other client errors are our fault and are passed on
+            Err(
+                e @ (handle_error::Error::ReqwestMiddlewareAPIError(_)
+                | handle_error::Error::ReqwestAPIError(_)
+                | handle_error::Error::ServerError(_)
+                | handle_error::Error::ClientError(handle_error::APILayerError {
+                    status: 401 | 403 | 429,
+                    ..
+                })),
+            ) => {
+                tracing::event!(tracing::Level::ERROR, "{}", e);
+                self.circuit_breaker.record_failure();
+                self.unavailable(contents)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn unavailable(
+        &self,
+        contents: Vec<String>,
+    ) -> Result<Vec<ProfanityCheck>, handle_error::Error> {
+        match self.config.failure_mode {
+            FailureMode::Open => Ok(contents
+                .into_iter()
+                .map(ProfanityCheck::unchecked)
+                .collect()),
+            FailureMode::Closed => Err(handle_error::Error::ProfanityServiceUnavailable),
+        }
+    }
+
+    /// Joins `contents` with `BATCH_SEPARATOR` into one request and splits the censored
+    /// result again. Falls back to one request per content if the contents cannot be
+    /// separated reliably.
+    async fn request_batch(
+        &self,
+        contents: &[String],
+    ) -> Result<Vec<ProfanityCheck>, handle_error::Error> {
+        if contents.len() > 1 && !contents.iter().any(|c| c.contains(BATCH_SEPARATOR)) {
+            let res = self.request(contents.join(BATCH_SEPARATOR)).await?;
+            if let Some(checks) = split_batch(contents, &res.censored_content, res.bad_words_list) {
+                return Ok(checks);
+            }
+            tracing::event!(
+                tracing::Level::WARN,
+                "Cannot split batched profanity result, checking contents one by one"
+            );
+        }
+
+        let mut checks = Vec::with_capacity(contents.len());
+        for content in contents {
+            let res = self.request(content.clone()).await?;
+            checks.push(ProfanityCheck {
+                content: content.clone(),
+                censored_content: res.censored_content,
+                bad_words_total: res.bad_words_total,
+                bad_words: res
+                    .bad_words_list
+                    .into_iter()
+                    .map(|bad_word| bad_word.original)
+                    .collect(),
+                unchecked: false,
+            });
+        }
+        Ok(checks)
+    }
+
+    async fn request(&self, content: String) -> Result<BadWordsResponse, handle_error::Error> {
+        let started = Instant::now();
+        let res = self.send(content).await;
+
+        let outcome = match &res {
+            Ok(_) => "success",
+            Err(handle_error::Error::ClientError(_)) => "client_error",
+            Err(handle_error::Error::ServerError(_)) => "server_error",
+            Err(_) => "transport_error",
+        };
+        metrics::profanity_request(outcome, started.elapsed());
+
+        res
+    }
+
+    async fn send(&self, content: String) -> Result<BadWordsResponse, handle_error::Error> {
+        let res = self
+            .client
+            .post(&self.endpoint)
+            .header("apiKey", &self.api_key)
+            .body(content)
+            .send()
+            .await
+            .map_err(handle_error::Error::ReqwestMiddlewareAPIError)?;
+
+        if !res.status().is_success() {
+            if res.status().is_client_error() {
+                let err = transform_error(res).await;
+                return Err(handle_error::Error::ClientError(err));
+            } else {
+                let err = transform_error(res).await;
+                return Err(handle_error::Error::ServerError(err));
+            }
+        }
+
+        res.json::<BadWordsResponse>()
+            .await
+            .map_err(handle_error::Error::ReqwestAPIError)
+    }
+}
+
+/// Maps the result of a batched request back to the individual contents, or `None` if it
+/// cannot be done reliably. Bad words are attributed by their offset if the word is found
+/// there, counting either characters or bytes as the API does not document which. Otherwise
+/// they are attributed to the only content containing them.
+fn split_batch(
+    contents: &[String],
+    censored_content: &str,
+    bad_words: Vec<BadWord>,
+) -> Option<Vec<ProfanityCheck>> {
+    let censored: Vec<&str> = censored_content.split(BATCH_SEPARATOR).collect();
+    if censored.len() != contents.len() {
+        return None;
+    }
+
+    let joined = contents.join(BATCH_SEPARATOR);
+    let mut char_ranges = Vec::with_capacity(contents.len());
+    let mut byte_ranges = Vec::with_capacity(contents.len());
+    let (mut char_start, mut byte_start) = (0, 0);
+    for content in contents {
+        let (char_end, byte_end) = (
+            char_start + content.chars().count(),
+            byte_start + content.len(),
+        );
+        char_ranges.push(char_start..char_end);
+        byte_ranges.push(byte_start..byte_end);
+        char_start = char_end + BATCH_SEPARATOR.chars().count();
+        byte_start = byte_end + BATCH_SEPARATOR.len();
     }
+
+    let mut checks: Vec<ProfanityCheck> = contents
+        .iter()
+        .zip(censored)
+        .map(|(content, censored)| ProfanityCheck {
+            content: content.clone(),
+            censored_content: censored.to_string(),
+            bad_words_total: 0,
+            bad_words: vec![],
+            unchecked: false,
+        })
+        .collect();
+
+    for bad_word in bad_words {
+        let original = bad_word.original.as_str();
+        let at_char = bad_word.start.filter(|&start| {
+            joined
+                .chars()
+                .skip(start)
+                .take(original.chars().count())
+                .eq(original.chars())
+        });
+        let at_byte = bad_word
+            .start
+            .filter(|&start| joined.get(start..start + original.len()) == Some(original));
+
+        let index = if let Some(start) = at_char {
+            char_ranges.iter().position(|range| range.contains(&start))
+        } else if let Some(start) = at_byte {
+            byte_ranges.iter().position(|range| range.contains(&start))
+        } else {
+            let mut containing = (0..contents.len()).filter(|&i| contents[i].contains(original));
+            match (containing.next(), containing.next()) {
+                (Some(index), None) => Some(index),
+                _ => None,
+            }
+        };
+
+        let check = checks.get_mut(index?)?;
+        check.bad_words_total += 1;
+        check.bad_words.push(bad_word.original);
+    }
+
+    // Censored content always means bad words, even if they could not be attributed
+    for check in checks.iter_mut() {
+        if check.bad_words_total == 0 && check.censored_content != check.content {
+            check.bad_words_total = 1;
+        }
+    }
+
+    Some(checks)
 }
 
 async fn transform_error(res: reqwest::Response) -> handle_error::APILayerError {
+    let status = res.status();
+    // The body is not guaranteed to be the documented JSON, e.g. behind a proxy
+    let message = match res.text().await {
+        Ok(body) => match serde_json::from_str::<APIResponse>(&body) {
+            Ok(res) => res.message,
+            Err(_) if !body.is_empty() => body,
+            Err(_) => status.canonical_reason().unwrap_or_default().to_string(),
+        },
+        Err(e) => e.to_string(),
+    };
+
     handle_error::APILayerError {
-        status: res.status().as_u16(),
-        message: res.json::<APIResponse>().await.unwrap().message,
+        status: status.as_u16(),
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(content: &str, bad_words: &[&str]) -> ProfanityCheck {
+        ProfanityCheck {
+            content: content.to_string(),
+            censored_content: content.to_string(),
+            bad_words_total: bad_words.len() as i64,
+            bad_words: bad_words.iter().map(|word| word.to_string()).collect(),
+            unchecked: false,
+        }
+    }
+
+    fn bad_word(original: &str, start: Option<usize>) -> BadWord {
+        BadWord {
+            original: original.to_string(),
+            word: original.to_lowercase(),
+            deviations: 0,
+            info: 2,
+            start,
+            replaced_len: original.chars().count() as i64,
+        }
+    }
+
+    fn contents(contents: &[&str]) -> Vec<String> {
+        contents.iter().map(|content| content.to_string()).collect()
+    }
+
+    fn censored(contents: &[&str]) -> String {
+        contents.join(BATCH_SEPARATOR)
+    }
+
+    fn bad_words(checks: &[ProfanityCheck]) -> Vec<(i64, Vec<String>)> {
+        checks
+            .iter()
+            .map(|check| (check.bad_words_total, check.bad_words.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn splits_by_character_offsets_in_multibyte_text() {
+        let contents = contents(&["Grüße aus Köln, darn", "Ärger über darn"]);
+        let offset = "Grüße aus Köln, darn".chars().count() + BATCH_SEPARATOR.len();
+        let checks = split_batch(
+            &contents,
+            &censored(&["Grüße aus Köln, ****", "Ärger über ****"]),
+            vec![
+                bad_word("darn", Some(16)),
+                bad_word("darn", Some(offset + 11)),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(checks[0].censored_content, "Grüße aus Köln, ****");
+        assert_eq!(checks[1].censored_content, "Ärger über ****");
+        assert_eq!(
+            bad_words(&checks),
+            vec![(1, vec!["darn".to_string()]), (1, vec!["darn".to_string()])]
+        );
+    }
+
+    #[test]
+    fn splits_by_byte_offsets_in_multibyte_text() {
+        let contents = contents(&["Grüße", "Köln darn"]);
+        // Only counting bytes puts the word at this offset
+        let offset = "Grüße".len() + BATCH_SEPARATOR.len() + "Köln ".len();
+        let checks = split_batch(
+            &contents,
+            &censored(&["Grüße", "Köln ****"]),
+            vec![bad_word("darn", Some(offset))],
+        )
+        .unwrap();
+
+        assert_eq!(
+            bad_words(&checks),
+            vec![(0, vec![]), (1, vec!["darn".to_string()])]
+        );
+    }
+
+    #[test]
+    fn attributes_bad_words_next_to_the_separator() {
+        let contents = contents(&["title darn", "darn content"]);
+        let second = "title darn".len() + BATCH_SEPARATOR.len();
+        let checks = split_batch(
+            &contents,
+            &censored(&["title ****", "**** content"]),
+            vec![bad_word("darn", Some(6)), bad_word("darn", Some(second))],
+        )
+        .unwrap();
+
+        assert_eq!(checks[0].censored_content, "title ****");
+        assert_eq!(checks[1].censored_content, "**** content");
+        assert_eq!(
+            bad_words(&checks),
+            vec![(1, vec!["darn".to_string()]), (1, vec!["darn".to_string()])]
+        );
+    }
+
+    #[test]
+    fn cannot_split_a_mismatched_count() {
+        let contents = contents(&["title", "content"]);
+        assert!(split_batch(&contents, "title content", vec![]).is_none());
+        assert!(split_batch(&contents, &censored(&["title", "content", "more"]), vec![]).is_none());
+    }
+
+    #[test]
+    fn attributes_bad_words_without_offset_to_the_only_content_containing_them() {
+        let contents = contents(&["title", "darn content"]);
+        let checks = split_batch(
+            &contents,
+            &censored(&["title", "**** content"]),
+            vec![bad_word("darn", None)],
+        )
+        .unwrap();
+
+        assert_eq!(
+            bad_words(&checks),
+            vec![(0, vec![]), (1, vec!["darn".to_string()])]
+        );
+    }
+
+    #[test]
+    fn cannot_split_ambiguous_bad_words() {
+        let contents = contents(&["darn title", "darn content"]);
+        // The offset points at neither occurrence
+        assert!(split_batch(
+            &contents,
+            &censored(&["**** title", "**** content"]),
+            vec![bad_word("darn", Some(3))],
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn censored_content_counts_as_bad_word() {
+        let contents = contents(&["title", "darn content"]);
+        let checks = split_batch(&contents, &censored(&["title", "**** content"]), vec![]).unwrap();
+        assert_eq!(bad_words(&checks), vec![(0, vec![]), (1, vec![])]);
+    }
+
+    #[test]
+    fn review_reason_counts_bad_words_of_all_checks() {
+        let title = check("title", &["shit"]);
+        let content = check("content", &["damn", "hell"]);
+        assert_eq!(
+            ProfanityCheck::review_reason(&[&title, &content]),
+            Some("3 bad words found".to_string())
+        );
+    }
+
+    #[test]
+    fn review_reason_flags_unchecked_content() {
+        let title = check("title", &[]);
+        let content = ProfanityCheck::unchecked("content".to_string());
+        assert!(ProfanityCheck::review_reason(&[&title, &content])
+            .is_some_and(|reason| reason.contains("unavailable")));
+    }
+
+    #[test]
+    fn clean_content_needs_no_review() {
+        let content = check("content", &[]);
+        assert_eq!(ProfanityCheck::review_reason(&[&content]), None);
     }
 }
@@ -0,0 +1,340 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use warp::http::Method;
+use warp::path::FullPath;
+use warp::{Filter, Rejection, Reply};
+
+use crate::config::RateLimitConfig;
+use crate::routes::authentication::verify_token;
+use crate::spam::client_ip;
+use crate::store::Store;
+
+/// Number of buckets after which full buckets are dropped from memory
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Where the token buckets are kept
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitBackend {
+    /// In process memory, every instance limits on its own
+    #[default]
+    Memory,
+    /// In Postgres, shared by all instances
+    Postgres,
+}
+
+/// Token bucket holding up to `burst` requests, refilled by `per_minute` requests per minute
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct BucketLimit {
+    pub burst: u32,
+    pub per_minute: u32,
+}
+
+impl BucketLimit {
+    fn per_second(&self) -> f64 {
+        self.per_minute as f64 / 60.0
+    }
+
+    fn refill(&self, tokens: f64, elapsed_secs: f64) -> f64 {
+        (tokens + elapsed_secs * self.per_second()).min(self.burst as f64)
+    }
+
+    /// Seconds until `tokens` reach `target`
+    fn secs_until(&self, tokens: f64, target: f64) -> u64 {
+        if tokens >= target || self.per_minute == 0 {
+            return 0;
+        }
+        ((target - tokens) / self.per_second()).ceil() as u64
+    }
+}
+
+/// Routes sharing a limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteGroup {
+    /// `GET` requests
+    Read,
+    /// Requests creating, changing or deleting data
+    Write,
+    /// Registration and login
+    Authentication,
+}
+
+impl RouteGroup {
+    fn of(method: &Method, path: &str) -> Self {
+        let first_segment = path.trim_start_matches('/').split('/').next();
+        match first_segment {
+            Some("registration") | Some("login") => RouteGroup::Authentication,
+            _ if method == Method::GET => RouteGroup::Read,
+            _ => RouteGroup::Write,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            RouteGroup::Read => "read",
+            RouteGroup::Write => "write",
+            RouteGroup::Authentication => "authentication",
+        }
+    }
+}
+
+/// Limit left after a request was let through, sent as `RateLimit-*` headers
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitStatus {
+    limit: u32,
+    remaining: u32,
+    reset: u64,
+}
+
+impl RateLimitStatus {
+    /// Adds the `RateLimit-*` headers to `reply`. Without a status, e.g. while rate limiting
+    /// is disabled, the reply is left unchanged.
+    pub fn apply(status: Option<RateLimitStatus>, reply: impl Reply) -> warp::reply::Response {
+        let mut response = reply.into_response();
+        if let Some(status) = status {
+            let headers = response.headers_mut();
+            headers.insert("RateLimit-Limit", status.limit.into());
+            headers.insert("RateLimit-Remaining", status.remaining.into());
+            headers.insert("RateLimit-Reset", status.reset.into());
+        }
+        response
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    /// Limit of the route group the bucket belongs to
+    limit: BucketLimit,
+}
+
+/// Token buckets kept in process memory
+#[derive(Debug, Default)]
+struct MemoryBuckets {
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl MemoryBuckets {
+    /// Returns the tokens left if a token was taken, or the tokens in the bucket if it was empty
+    fn take(&self, key: String, limit: BucketLimit) -> Result<f64, f64> {
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+
+        // Full buckets are dropped, each refilled by its own limit as route groups differ
+        if buckets.len() > PRUNE_THRESHOLD {
+            buckets.retain(|_, bucket| {
+                bucket.limit.refill(
+                    bucket.tokens,
+                    now.duration_since(bucket.updated).as_secs_f64(),
+                ) < bucket.limit.burst as f64
+            });
+        }
+
+        let bucket = buckets.entry(key).or_insert(Bucket {
+            tokens: limit.burst as f64,
+            updated: now,
+            limit,
+        });
+        let tokens = limit.refill(
+            bucket.tokens,
+            now.duration_since(bucket.updated).as_secs_f64(),
+        );
+        bucket.tokens = tokens;
+        bucket.updated = now;
+        bucket.limit = limit;
+
+        if tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(bucket.tokens)
+        } else {
+            Err(tokens)
+        }
+    }
+}
+
+/// Token buckets per client and route group. Clients are identified by their account if they
+/// send a valid token, otherwise by their IP address.
+/// Cloning is cheap, all clones share the buckets.
+#[derive(Debug, Clone)]
+pub struct RateLimit {
+    pub config: RateLimitConfig,
+    store: Store,
+    buckets: Arc<MemoryBuckets>,
+}
+
+impl RateLimit {
+    pub fn new(config: RateLimitConfig, store: Store) -> Self {
+        RateLimit {
+            config,
+            store,
+            buckets: Arc::new(MemoryBuckets::default()),
+        }
+    }
+
+    fn limit(&self, group: RouteGroup) -> BucketLimit {
+        match group {
+            RouteGroup::Read => self.config.read,
+            RouteGroup::Write => self.config.write,
+            RouteGroup::Authentication => self.config.authentication,
+        }
+    }
+
+    /// Takes a token from the bucket of `key`, or fails with `Error::TooManyRequests`
+    async fn acquire(
+        &self,
+        key: String,
+        limit: BucketLimit,
+    ) -> Result<RateLimitStatus, handle_error::Error> {
+        let taken = match self.config.backend {
+            RateLimitBackend::Memory => Ok(self.buckets.take(key, limit)),
+            RateLimitBackend::Postgres => {
+                self.store
+                    .take_rate_limit_token(&key, limit.burst as f64, limit.per_second())
+                    .await
+            }
+        };
+
+        match taken {
+            Ok(Ok(tokens)) => Ok(RateLimitStatus {
+                limit: limit.burst,
+                remaining: tokens.floor() as u32,
+                reset: limit.secs_until(tokens, limit.burst as f64),
+            }),
+            Ok(Err(tokens)) => Err(handle_error::Error::TooManyRequests(
+                handle_error::RateLimited {
+                    limit: limit.burst,
+                    retry_after: limit.secs_until(tokens, 1.0).max(1),
+                    reset: limit.secs_until(tokens, limit.burst as f64),
+                },
+            )),
+            // Requests are let through while the shared buckets are unavailable,
+            // the error is logged by the store
+            Err(_) => Ok(RateLimitStatus {
+                limit: limit.burst,
+                remaining: limit.burst,
+                reset: 0,
+            }),
+        }
+    }
+}
+
+/// Rate limits every request by route group. Extracts the status of the limit to be added to
+/// the response with `RateLimitStatus::apply`.
+pub fn rate_limit(
+    rate_limit: RateLimit,
+) -> impl Filter<Extract = (Option<RateLimitStatus>,), Error = Rejection> + Clone {
+    warp::any()
+        .map(move || rate_limit.clone())
+        .and(warp::method())
+        .and(warp::path::full())
+        .and(warp::header::optional::<String>("Authorization"))
+        .and(client_ip())
+        .and_then(
+            |rate_limit: RateLimit,
+             method: Method,
+             path: FullPath,
+             token: Option<String>,
+             ip: Option<IpAddr>| async move {
+                if !rate_limit.config.enabled {
+                    return Ok(None);
+                }
+
+                let group = RouteGroup::of(&method, path.as_str());
+                // Invalid tokens are rejected by the routes, here they count against the IP
+                let client = match token.map(verify_token) {
+                    Some(Ok(session)) => format!("account:{}", session.account_id.0),
+                    _ => match ip {
+                        Some(ip) => format!("ip:{}", ip),
+                        None => "ip:unknown".to_string(),
+                    },
+                };
+
+                rate_limit
+                    .acquire(
+                        format!("{}:{}", group.as_str(), client),
+                        rate_limit.limit(group),
+                    )
+                    .await
+                    .map(Some)
+                    .map_err(warp::reject::custom)
+            },
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const READ: BucketLimit = BucketLimit {
+        burst: 60,
+        per_minute: 60,
+    };
+    const AUTHENTICATION: BucketLimit = BucketLimit {
+        burst: 2,
+        per_minute: 6,
+    };
+
+    #[test]
+    fn refill_is_capped_at_burst() {
+        assert_eq!(AUTHENTICATION.refill(0.0, 5.0), 0.5);
+        assert_eq!(AUTHENTICATION.refill(1.5, 10.0), 2.0);
+        assert_eq!(READ.refill(10.0, 1.0), 11.0);
+    }
+
+    #[test]
+    fn secs_until_rounds_up() {
+        assert_eq!(AUTHENTICATION.secs_until(0.0, 1.0), 10);
+        assert_eq!(AUTHENTICATION.secs_until(0.5, 1.0), 5);
+        assert_eq!(AUTHENTICATION.secs_until(0.95, 2.0), 11);
+        assert_eq!(AUTHENTICATION.secs_until(2.0, 1.0), 0);
+        let never = BucketLimit {
+            burst: 1,
+            per_minute: 0,
+        };
+        assert_eq!(never.secs_until(0.0, 1.0), 0);
+    }
+
+    /// Tokens left after taking one, a few microseconds of refill aside
+    fn taken(result: Result<f64, f64>) -> f64 {
+        result.expect("Token taken").floor()
+    }
+
+    #[test]
+    fn takes_tokens_until_the_bucket_is_empty() {
+        let buckets = MemoryBuckets::default();
+        assert_eq!(taken(buckets.take("a".to_string(), AUTHENTICATION)), 1.0);
+        assert_eq!(taken(buckets.take("a".to_string(), AUTHENTICATION)), 0.0);
+        assert!(buckets
+            .take("a".to_string(), AUTHENTICATION)
+            .is_err_and(|tokens| tokens < 1.0));
+        // Other keys have their own bucket
+        assert_eq!(taken(buckets.take("b".to_string(), AUTHENTICATION)), 1.0);
+    }
+
+    #[test]
+    fn prune_drops_only_buckets_full_by_their_own_limit() {
+        let buckets = MemoryBuckets::default();
+        // Partially drained read buckets stay below their burst for a second
+        for i in 0..PRUNE_THRESHOLD {
+            taken(buckets.take(format!("read:{}", i), READ));
+        }
+        // Full again after a few milliseconds
+        let fast = BucketLimit {
+            burst: 2,
+            per_minute: 6000,
+        };
+        taken(buckets.take("fast".to_string(), fast));
+        std::thread::sleep(std::time::Duration::from_millis(50));
+
+        // Pruning on behalf of another route group must not reset the read buckets
+        taken(buckets.take("authentication".to_string(), AUTHENTICATION));
+        let buckets = buckets.buckets.lock().unwrap();
+        assert!(!buckets.contains_key("fast"));
+        assert!(buckets.contains_key("read:0"));
+        assert_eq!(buckets.len(), PRUNE_THRESHOLD + 1);
+    }
+}
@@ -1,26 +1,167 @@
-use crate::profanity;
+use crate::jobs::{self, RecomputeReputation};
+use crate::profanity::{Profanity, ProfanityCheck};
+use crate::spam::Spam;
 use crate::store;
 use crate::types::account::Session;
 use crate::types::answer::NewAnswer;
+use crate::types::moderation::{ModerationSource, NewModerationItem, Visibility};
+use crate::types::notification::NotificationKind;
+use crate::types::reputation::{ReputationEvent, ReputationEventKind};
+use crate::types::webhook::WebhookEvent;
+use std::net::IpAddr;
 use warp::{http::StatusCode, Rejection, Reply};
 
 pub async fn add_answer(
     store: store::Store,
     session: Session,
+    profanity: Profanity,
+    spam: Spam,
+    ip: Option<IpAddr>,
     new_answer: NewAnswer,
 ) -> Result<impl Reply, Rejection> {
-    let content = match profanity::check_profanity(new_answer.content).await {
+    store
+        .require_visible_question(new_answer.question_id.0)
+        .await?;
+    if store.is_question_closed(new_answer.question_id.0).await? {
+        return Err(warp::reject::custom(handle_error::Error::QuestionClosed));
+    }
+
+    let suspect = spam
+        .assess(&store, &session.account_id, &new_answer.content)
+        .await?;
+
+    let content = match profanity.check(new_answer.content).await {
         Ok(res) => res,
         Err(e) => return Err(warp::reject::custom(e)),
     };
 
+    let held = profanity.config.answer.holds(&content) || suspect.is_some();
+    let visibility = if held {
+        Visibility::Pending
+    } else {
+        Visibility::Visible
+    };
     let answer = NewAnswer {
-        content,
+        content: profanity.config.answer.apply(&content)?,
         question_id: new_answer.question_id,
     };
 
-    match store.add_answer(answer, session.account_id).await {
-        Ok(_) => Ok(warp::reply::with_status("Answer added", StatusCode::OK)),
+    // Only posts which passed validation count against the posting rate
+    spam.check_post_rate(&store, &session.account_id, ip)
+        .await?;
+
+    // The answer is only stored together with its moderation items, so a failure cannot
+    // leave a stored answer behind that the client then posts again
+    let transaction = store.begin().await?;
+    match transaction
+        .add_answer(answer, session.account_id.clone(), visibility)
+        .await
+    {
+        Ok(answer) => {
+            // Bad words and content accepted unchecked while failing open are reviewed
+            if let Some(reason) = ProfanityCheck::review_reason(&[&content]) {
+                transaction
+                    .enqueue_moderation_item(NewModerationItem {
+                        question_id: None,
+                        answer_id: Some(answer.id.clone()),
+                        comment_id: None,
+                        source: ModerationSource::ProfanityFilter,
+                        reason: Some(reason),
+                        reported_by: None,
+                    })
+                    .await?;
+            }
+            if let Some(reason) = suspect {
+                transaction
+                    .enqueue_moderation_item(NewModerationItem {
+                        question_id: None,
+                        answer_id: Some(answer.id.clone()),
+                        comment_id: None,
+                        source: ModerationSource::SpamFilter,
+                        reason: Some(reason),
+                        reported_by: None,
+                    })
+                    .await?;
+            }
+            transaction.commit().await?;
+
+            if held {
+                return Ok(warp::reply::with_status(
+                    "Answer held for review",
+                    StatusCode::ACCEPTED,
+                ));
+            }
+
+            // The answer is stored already, a failed notification is logged by the store
+            // but must not fail the request
+            let _ = store
+                .notify_followers(
+                    NotificationKind::NewAnswer,
+                    answer.question_id.0,
+                    Some(answer.id.0),
+                    None,
+                    &session.account_id,
+                )
+                .await;
+            let _ = store
+                .enqueue_webhook_deliveries(WebhookEvent::AnswerAdded, serde_json::json!(answer))
+                .await;
+            Ok(warp::reply::with_status("Answer added", StatusCode::OK))
+        }
         Err(e) => Err(warp::reject::custom(e)),
     }
 }
+
+pub async fn accept_answer(
+    id: i32,
+    session: Session,
+    store: store::Store,
+) -> Result<impl Reply, Rejection> {
+    store.require_visible_answer(id).await?;
+    let answer = store.get_answer(id).await?;
+    let question_id = answer.question_id.0;
+    if !store
+        .is_question_owner(question_id, &session.account_id)
+        .await?
+    {
+        return Err(warp::reject::custom(handle_error::Error::Unauthorized));
+    }
+
+    let author = answer
+        .author
+        .clone()
+        .map(|author| author.id)
+        .ok_or(handle_error::Error::AnswerNotFound)?;
+
+    // The accepted answer and the reputation ledger change together, the reputation of its
+    // author is recomputed once committed
+    let transaction = store.begin().await?;
+    transaction.accept_answer(question_id, id).await?;
+
+    // Keyed by question, so accepting a different answer moves the points to its author
+    let event = ReputationEvent::new(
+        ReputationEventKind::AnswerAccepted,
+        author,
+        session.account_id,
+        Some(question_id),
+        None,
+    );
+    let affected = transaction
+        .replace_reputation_event(event, &[ReputationEventKind::AnswerAccepted])
+        .await?;
+    jobs::enqueue(
+        &transaction,
+        RecomputeReputation {
+            account_ids: affected,
+        },
+    )
+    .await?;
+    transaction.commit().await?;
+
+    // Failures are logged by the store, but must not fail the request
+    let _ = store
+        .enqueue_webhook_deliveries(WebhookEvent::AnswerAccepted, serde_json::json!(answer))
+        .await;
+
+    Ok(warp::reply::with_status("Answer accepted", StatusCode::OK))
+}
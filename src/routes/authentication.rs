@@ -1,4 +1,5 @@
 use std::future;
+use std::net::IpAddr;
 
 use argon2::{self, Config};
 use chrono::{Duration, Utc};
@@ -6,11 +7,19 @@ use rand::Rng;
 use warp::{Filter, Rejection, Reply};
 
 use crate::{
+    spam::Spam,
     store::Store,
     types::account::{Account, AccountId, Session},
 };
 
-pub async fn register(store: Store, account: Account) -> Result<impl Reply, Rejection> {
+pub async fn register(
+    store: Store,
+    spam: Spam,
+    ip: Option<IpAddr>,
+    account: Account,
+) -> Result<impl Reply, Rejection> {
+    spam.check_registration_rate(ip)?;
+
     let hashed_password = hash_password(account.password.as_bytes());
 
     let account = Account {
@@ -72,7 +81,7 @@ fn issue_token(account_id: AccountId) -> String {
         .expect("Failed to construct paseto token.")
 }
 
-fn verify_token(token: String) -> Result<Session, handle_error::Error> {
+pub fn verify_token(token: String) -> Result<Session, handle_error::Error> {
     let token = paseto::tokens::validate_local_token(
         &token,
         None,
@@ -94,3 +103,17 @@ pub fn auth() -> impl Filter<Extract = (Session,), Error = Rejection> + Clone {
         future::ready(Ok(token))
     })
 }
+
+/// Like `auth`, but lets anonymous requests through with `None`
+pub fn optional_auth() -> impl Filter<Extract = (Option<Session>,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("Authorization").and_then(|token: Option<String>| {
+        let token = match token.map(verify_token) {
+            None => None,
+            Some(Ok(t)) => Some(t),
+            // Rejecting without a cause would end up as `404 Route not found`
+            Some(Err(e)) => return future::ready(Err(warp::reject::custom(e))),
+        };
+
+        future::ready(Ok(token))
+    })
+}
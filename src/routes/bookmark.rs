@@ -0,0 +1,51 @@
+use crate::store;
+use crate::types::account::Session;
+use crate::types::pagination::{extract_pagination, Pagination};
+use std::collections::HashMap;
+use warp::{http::StatusCode, Rejection, Reply};
+
+pub async fn add_bookmark(
+    id: i32,
+    session: Session,
+    store: store::Store,
+) -> Result<impl Reply, Rejection> {
+    // Respond with 404 rather than a foreign key violation for unknown questions, hidden
+    // questions cannot be told apart from those
+    store.require_visible_question(id).await?;
+
+    match store.add_bookmark(id, &session.account_id).await {
+        Ok(_) => Ok(warp::reply::with_status("Bookmark added", StatusCode::OK)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn delete_bookmark(
+    id: i32,
+    session: Session,
+    store: store::Store,
+) -> Result<impl Reply, Rejection> {
+    match store.delete_bookmark(id, &session.account_id).await {
+        Ok(_) => Ok(warp::reply::with_status("Bookmark deleted", StatusCode::OK)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn get_bookmarks(
+    params: HashMap<String, String>,
+    session: Session,
+    store: store::Store,
+) -> Result<impl Reply, Rejection> {
+    let mut pagination = Pagination::default();
+
+    if !params.is_empty() {
+        pagination = extract_pagination(params)?;
+    }
+
+    match store
+        .get_bookmarked_questions(&session.account_id, pagination.limit, pagination.offset)
+        .await
+    {
+        Ok(questions) => Ok(warp::reply::json(&questions)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
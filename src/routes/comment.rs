@@ -0,0 +1,81 @@
+use crate::profanity::{Profanity, ProfanityCheck};
+use crate::store;
+use crate::types::account::Session;
+use crate::types::comment::NewComment;
+use crate::types::moderation::{ModerationSource, NewModerationItem};
+use crate::types::notification::NotificationKind;
+use crate::types::reputation::Privilege;
+use warp::{Rejection, Reply};
+
+pub async fn get_comments(id: i32, store: store::Store) -> Result<impl Reply, Rejection> {
+    match store.get_comments(id).await {
+        Ok(comments) => Ok(warp::reply::json(&comments)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn add_comment(
+    id: i32,
+    session: Session,
+    store: store::Store,
+    profanity: Profanity,
+    new_comment: NewComment,
+) -> Result<impl Reply, Rejection> {
+    store.require_visible_question(id).await?;
+    // Everybody may comment on their own question
+    let author = store.get_question_author(id).await?;
+    if author != session.account_id {
+        store
+            .require_privilege(&session.account_id, Privilege::Comment)
+            .await?;
+    }
+
+    let content = match profanity.check(new_comment.content).await {
+        Ok(res) => res,
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
+
+    // The comment is only stored together with its moderation item, so a failure cannot
+    // leave a stored comment behind that the client then posts again
+    let transaction = store.begin().await?;
+    match transaction
+        .add_comment(
+            id,
+            NewComment {
+                content: content.censored_content.clone(),
+            },
+            session.account_id.clone(),
+        )
+        .await
+    {
+        Ok(comment) => {
+            // Bad words and content accepted unchecked while failing open are reviewed
+            if let Some(reason) = ProfanityCheck::review_reason(&[&content]) {
+                transaction
+                    .enqueue_moderation_item(NewModerationItem {
+                        question_id: None,
+                        answer_id: None,
+                        comment_id: Some(comment.id.clone()),
+                        source: ModerationSource::ProfanityFilter,
+                        reason: Some(reason),
+                        reported_by: None,
+                    })
+                    .await?;
+            }
+            transaction.commit().await?;
+
+            // A failed notification is logged by the store but must not fail the request
+            let _ = store
+                .notify_followers(
+                    NotificationKind::NewComment,
+                    id,
+                    None,
+                    Some(comment.id.0),
+                    &session.account_id,
+                )
+                .await;
+            Ok(warp::reply::json(&comment))
+        }
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
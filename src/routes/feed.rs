@@ -0,0 +1,58 @@
+use futures_util::{SinkExt, Stream, StreamExt};
+use std::convert::Infallible;
+use warp::sse::Event;
+use warp::ws::{Message, WebSocket, Ws};
+use warp::Reply;
+
+use crate::feed::Feed;
+use crate::types::feed::{FeedEvent, FeedFilter};
+
+/// Streams new and updated questions and answers as server-sent events
+/// # Example query
+/// `/feed?tag=rust` or `/feed?question_id=1`
+pub async fn get_feed_events(filter: FeedFilter, feed: Feed) -> Result<impl Reply, Infallible> {
+    let events = feed.subscribe(filter).map(|event| {
+        Event::default()
+            .event(event.kind.as_str())
+            .json_data(&event)
+    });
+
+    Ok(warp::sse::reply(warp::sse::keep_alive().stream(events)))
+}
+
+/// Like `get_feed_events`, but sends every event as JSON text message over a WebSocket
+pub async fn get_feed_socket(
+    filter: FeedFilter,
+    feed: Feed,
+    ws: Ws,
+) -> Result<impl Reply, Infallible> {
+    Ok(ws.on_upgrade(move |socket| send_events(socket, feed.subscribe(filter))))
+}
+
+async fn send_events(socket: WebSocket, events: impl Stream<Item = FeedEvent>) {
+    let (mut sender, mut receiver) = socket.split();
+    let mut events = Box::pin(events);
+
+    loop {
+        tokio::select! {
+            event = events.next() => {
+                let text = match event.map(|event| serde_json::to_string(&event)) {
+                    Some(Ok(text)) => text,
+                    Some(Err(e)) => {
+                        tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                        continue;
+                    }
+                    None => break,
+                };
+                if sender.send(Message::text(text)).await.is_err() {
+                    break;
+                }
+            }
+            // Messages of the client are ignored, the socket is only watched for closing
+            message = receiver.next() => match message {
+                Some(Ok(message)) if !message.is_close() => {}
+                _ => break,
+            },
+        }
+    }
+}
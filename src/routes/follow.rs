@@ -0,0 +1,57 @@
+use crate::store;
+use crate::types::account::Session;
+use warp::{http::StatusCode, Rejection, Reply};
+
+pub async fn follow_question(
+    id: i32,
+    session: Session,
+    store: store::Store,
+) -> Result<impl Reply, Rejection> {
+    // Respond with 404 rather than a foreign key violation for unknown questions, hidden
+    // questions cannot be told apart from those
+    store.require_visible_question(id).await?;
+
+    match store.follow_question(id, &session.account_id).await {
+        Ok(_) => Ok(warp::reply::with_status(
+            "Question followed",
+            StatusCode::OK,
+        )),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn unfollow_question(
+    id: i32,
+    session: Session,
+    store: store::Store,
+) -> Result<impl Reply, Rejection> {
+    match store.unfollow_question(id, &session.account_id).await {
+        Ok(_) => Ok(warp::reply::with_status(
+            "Question unfollowed",
+            StatusCode::OK,
+        )),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn follow_tag(
+    tag: String,
+    session: Session,
+    store: store::Store,
+) -> Result<impl Reply, Rejection> {
+    match store.follow_tag(tag, &session.account_id).await {
+        Ok(_) => Ok(warp::reply::with_status("Tag followed", StatusCode::OK)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn unfollow_tag(
+    tag: String,
+    session: Session,
+    store: store::Store,
+) -> Result<impl Reply, Rejection> {
+    match store.unfollow_tag(tag, &session.account_id).await {
+        Ok(_) => Ok(warp::reply::with_status("Tag unfollowed", StatusCode::OK)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
@@ -0,0 +1,54 @@
+use crate::profanity::Profanity;
+use crate::store;
+use crate::types::health::{CheckStatus, Readiness, VersionInfo};
+use std::convert::Infallible;
+use warp::{http::StatusCode, Reply};
+
+/// The process is up and serving requests
+pub async fn get_liveness() -> Result<impl Reply, Infallible> {
+    Ok(warp::reply::with_status("OK", StatusCode::OK))
+}
+
+/// The service can handle requests: Postgres is reachable and, if enabled, the profanity
+/// upstream is not cut off by the circuit breaker. Responds with `503` otherwise.
+pub async fn get_readiness(
+    store: store::Store,
+    profanity: Profanity,
+    check_profanity: bool,
+) -> Result<impl Reply, Infallible> {
+    let database = match store.ping().await {
+        Ok(_) => CheckStatus::Ok,
+        Err(_) => CheckStatus::Unavailable,
+    };
+    let profanity = check_profanity.then(|| {
+        if profanity.is_available() {
+            CheckStatus::Ok
+        } else {
+            CheckStatus::Unavailable
+        }
+    });
+
+    let ready = database == CheckStatus::Ok && profanity != Some(CheckStatus::Unavailable);
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    Ok(warp::reply::with_status(
+        warp::reply::json(&Readiness {
+            ready,
+            database,
+            profanity,
+        }),
+        status,
+    ))
+}
+
+pub async fn get_version(store: store::Store) -> Result<impl Reply, Infallible> {
+    Ok(warp::reply::json(&VersionInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        git_hash: env!("GIT_HASH"),
+        migration: store.get_migration_version().await.unwrap_or(None),
+    }))
+}
@@ -0,0 +1,13 @@
+use crate::metrics;
+use crate::store;
+use std::convert::Infallible;
+use warp::Reply;
+
+/// Metrics in the Prometheus text format
+pub async fn get_metrics(store: store::Store) -> Result<impl Reply, Infallible> {
+    Ok(warp::reply::with_header(
+        metrics::render(&store.connection),
+        "Content-Type",
+        "text/plain; version=0.0.4",
+    ))
+}
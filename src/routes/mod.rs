@@ -1,3 +1,14 @@
 pub mod answer;
 pub mod authentication;
+pub mod bookmark;
+pub mod comment;
+pub mod feed;
+pub mod follow;
+pub mod health;
+pub mod metrics;
+pub mod moderation;
+pub mod notification;
 pub mod question;
+pub mod user;
+pub mod vote;
+pub mod webhook;
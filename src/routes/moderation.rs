@@ -0,0 +1,183 @@
+use crate::store;
+use crate::types::account::Session;
+use crate::types::answer::AnswerId;
+use crate::types::moderation::{
+    ModerationDecision, ModerationResolution, ModerationSource, NewFlag, NewModerationAction,
+    NewModerationItem,
+};
+use crate::types::pagination::{extract_pagination, Pagination};
+use crate::types::question::QuestionId;
+use crate::types::reputation::Privilege;
+use std::collections::HashMap;
+use warp::{http::StatusCode, Rejection, Reply};
+
+pub async fn flag_question(
+    id: i32,
+    session: Session,
+    store: store::Store,
+    flag: NewFlag,
+) -> Result<impl Reply, Rejection> {
+    store.get_question_author(id).await?;
+
+    match store
+        .enqueue_moderation_item(NewModerationItem {
+            question_id: Some(QuestionId(id)),
+            answer_id: None,
+            comment_id: None,
+            source: ModerationSource::Flag,
+            reason: Some(flag.reason),
+            reported_by: Some(session.account_id),
+        })
+        .await
+    {
+        Ok(_) => Ok(warp::reply::with_status("Question flagged", StatusCode::OK)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn flag_answer(
+    id: i32,
+    session: Session,
+    store: store::Store,
+    flag: NewFlag,
+) -> Result<impl Reply, Rejection> {
+    store.get_answer(id).await?;
+
+    match store
+        .enqueue_moderation_item(NewModerationItem {
+            question_id: None,
+            answer_id: Some(AnswerId(id)),
+            comment_id: None,
+            source: ModerationSource::Flag,
+            reason: Some(flag.reason),
+            reported_by: Some(session.account_id),
+        })
+        .await
+    {
+        Ok(_) => Ok(warp::reply::with_status("Answer flagged", StatusCode::OK)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn get_moderation_queue(
+    params: HashMap<String, String>,
+    session: Session,
+    store: store::Store,
+) -> Result<impl Reply, Rejection> {
+    store
+        .require_privilege(&session.account_id, Privilege::ReviewModerationQueue)
+        .await?;
+
+    let mut pagination = Pagination::default();
+
+    if !params.is_empty() {
+        pagination = extract_pagination(params)?;
+    }
+
+    match store
+        .get_moderation_queue(pagination.limit, pagination.offset)
+        .await
+    {
+        Ok(items) => Ok(warp::reply::json(&items)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn get_moderation_actions(
+    id: i32,
+    session: Session,
+    store: store::Store,
+) -> Result<impl Reply, Rejection> {
+    store
+        .require_privilege(&session.account_id, Privilege::ReviewModerationQueue)
+        .await?;
+
+    match store.get_moderation_actions(id).await {
+        Ok(actions) => Ok(warp::reply::json(&actions)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Approves, edits or removes the question, answer or comment of a queued item and records the
+/// decision in the audit trail
+pub async fn resolve_moderation_item(
+    id: i32,
+    session: Session,
+    store: store::Store,
+    resolution: ModerationResolution,
+) -> Result<impl Reply, Rejection> {
+    store
+        .require_privilege(&session.account_id, Privilege::ReviewModerationQueue)
+        .await?;
+
+    let item = store.get_moderation_item(id).await?;
+    let status = resolution.decision.status();
+    let visibility = resolution.decision.visibility();
+
+    let (previous_title, previous_content) =
+        match (&item.question_id, &item.answer_id, &item.comment_id) {
+            (Some(question_id), _, _) => {
+                let previous = match resolution.decision {
+                    ModerationDecision::Edit { title, content } => {
+                        let (title, content) = store
+                            .edit_question_content(question_id.0, title, content)
+                            .await?;
+                        (Some(title), Some(content))
+                    }
+                    _ => (None, None),
+                };
+                store
+                    .set_question_visibility(question_id.0, visibility)
+                    .await?;
+                previous
+            }
+            (None, Some(answer_id), _) => {
+                let previous = match resolution.decision {
+                    ModerationDecision::Edit { content, .. } => {
+                        let content = store.edit_answer_content(answer_id.0, content).await?;
+                        (None, Some(content))
+                    }
+                    _ => (None, None),
+                };
+                store.set_answer_visibility(answer_id.0, visibility).await?;
+                previous
+            }
+            (None, None, Some(comment_id)) => {
+                let previous = match resolution.decision {
+                    ModerationDecision::Edit { content, .. } => {
+                        let content = store.edit_comment_content(comment_id.0, content).await?;
+                        (None, Some(content))
+                    }
+                    _ => (None, None),
+                };
+                store
+                    .set_comment_visibility(comment_id.0, visibility)
+                    .await?;
+                previous
+            }
+            (None, None, None) => {
+                return Err(warp::reject::custom(
+                    handle_error::Error::ModerationItemNotFound,
+                ))
+            }
+        };
+
+    store
+        .resolve_moderation_items(&item, status, &session.account_id)
+        .await?;
+
+    match store
+        .add_moderation_action(NewModerationAction {
+            item_id: item.id,
+            moderator_account_id: session.account_id,
+            action: status,
+            note: resolution.note,
+            previous_title,
+            previous_content,
+        })
+        .await
+    {
+        Ok(action) => Ok(warp::reply::json(&action)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
@@ -0,0 +1,64 @@
+use crate::store;
+use crate::types::account::Session;
+use crate::types::pagination::{extract_pagination, Pagination};
+use std::collections::HashMap;
+use warp::{http::StatusCode, Rejection, Reply};
+
+/// Lists notifications of the account, newest first
+/// # Example query
+/// `/notifications?unread=true&limit=10&offset=0`
+pub async fn get_notifications(
+    mut params: HashMap<String, String>,
+    session: Session,
+    store: store::Store,
+) -> Result<impl Reply, Rejection> {
+    let unread_only = params
+        .remove("unread")
+        .map(|unread| unread == "true")
+        .unwrap_or(false);
+    let mut pagination = Pagination::default();
+
+    if !params.is_empty() {
+        pagination = extract_pagination(params)?;
+    }
+
+    match store
+        .get_notifications(
+            &session.account_id,
+            unread_only,
+            pagination.limit,
+            pagination.offset,
+        )
+        .await
+    {
+        Ok(notifications) => Ok(warp::reply::json(&notifications)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn mark_notification_read(
+    id: i32,
+    session: Session,
+    store: store::Store,
+) -> Result<impl Reply, Rejection> {
+    match store.mark_notification_read(id, &session.account_id).await {
+        Ok(_) => Ok(warp::reply::with_status(
+            "Notification marked as read",
+            StatusCode::OK,
+        )),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn mark_all_notifications_read(
+    session: Session,
+    store: store::Store,
+) -> Result<impl Reply, Rejection> {
+    match store.mark_all_notifications_read(&session.account_id).await {
+        Ok(_) => Ok(warp::reply::with_status(
+            "Notifications marked as read",
+            StatusCode::OK,
+        )),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
@@ -1,42 +1,29 @@
-use crate::profanity::check_profanity;
+use crate::conditional::{self, Conditions};
+use crate::jobs::{self, RecomputeReputation};
+use crate::profanity::{Profanity, ProfanityCheck};
+use crate::spam::Spam;
 use crate::store;
 use crate::types::account::Session;
+use crate::types::moderation::{ModerationSource, NewModerationItem, Visibility};
 use crate::types::pagination::extract_pagination;
 use crate::types::pagination::Pagination;
 use crate::types::question::NewQuestion;
 use crate::types::question::Question;
-use serde::{Deserialize, Serialize};
+use crate::types::question::{CloseQuestion, CloseReason};
+use crate::types::reputation::Privilege;
+use crate::types::webhook::WebhookEvent;
 use std::collections::HashMap;
+use std::net::IpAddr;
 use tracing::{event, instrument, Level};
-use warp::{http::StatusCode, Rejection, Reply};
-
-#[derive(Deserialize, Serialize, Debug, Clone)]
-pub struct APIResponse {
-    message: String,
-}
-
-#[derive(Deserialize, Serialize, Debug, Clone)]
-struct BadWord {
-    original: String,
-    word: String,
-    deviations: i64,
-    info: i64,
-    #[serde(rename = "replacedLen")]
-    replaced_len: i64,
-}
-
-#[derive(Deserialize, Serialize, Debug, Clone)]
-struct BadWordsResponse {
-    content: String,
-    bad_words_total: i64,
-    bad_words_list: Vec<BadWord>,
-    censored_content: String,
-}
+use warp::http::{header, HeaderValue, StatusCode};
+use warp::{Rejection, Reply};
 
 #[instrument]
 pub async fn get_questions(
     params: HashMap<String, String>,
+    session: Option<Session>,
     store: store::Store,
+    conditions: Conditions,
 ) -> Result<impl Reply, Rejection> {
     event!(target: "rust_web_development", Level::INFO, "querying questions");
     let mut pagination = Pagination::default();
@@ -47,7 +34,11 @@ pub async fn get_questions(
     }
 
     let res: Vec<Question> = match store
-        .get_questions(pagination.limit, pagination.offset)
+        .get_questions(
+            pagination.limit,
+            pagination.offset,
+            session.map(|session| session.account_id),
+        )
         .await
     {
         Ok(res) => res,
@@ -56,46 +47,127 @@ pub async fn get_questions(
         }
     };
 
-    Ok(warp::reply::json(&res))
+    // No `Last-Modified`, questions which were deleted, hidden or moved to another page since
+    // would not change it and `If-Modified-Since` would keep serving the stale list
+    let mut response = conditions.reply(&res, None);
+    // Whether a question is bookmarked depends on the account
+    response
+        .headers_mut()
+        .insert(header::VARY, HeaderValue::from_static("Authorization"));
+    Ok(response)
 }
 
-// pub async fn get_question(id: i32, store: store::Store) -> Result<impl Reply, Rejection> {
-//     match store.questions.read().await.get(&QuestionId(id)) {
-//         Some(q) => Ok(warp::reply::json(&q)),
-//         None => Err(warp::reject::custom(Error::QuestionNotFound)),
-//     }
-// }
+pub async fn get_question(
+    id: i32,
+    session: Option<Session>,
+    store: store::Store,
+    conditions: Conditions,
+) -> Result<impl Reply, Rejection> {
+    let question = store
+        .get_question(id, session.map(|session| session.account_id))
+        .await?;
+    let mut response = conditions.reply(&question, question.updated_on);
+    // Whether the question is bookmarked depends on the account
+    response
+        .headers_mut()
+        .insert(header::VARY, HeaderValue::from_static("Authorization"));
+    Ok(response)
+}
 
 pub async fn add_question(
     session: Session,
     store: store::Store,
+    profanity: Profanity,
+    spam: Spam,
+    ip: Option<IpAddr>,
     new_question: NewQuestion,
 ) -> Result<impl Reply, Rejection> {
     let account_id = session.account_id;
-    let title = check_profanity(new_question.title);
-
-    let content = check_profanity(new_question.content);
+    let suspect = spam
+        .assess(&store, &account_id, &new_question.content)
+        .await?;
 
-    let (title, content) = tokio::join!(title, content);
-
-    let title = match title {
+    // Title and content are checked with a single upstream request
+    let [title, content] = match profanity
+        .check_all([new_question.title, new_question.content])
+        .await
+    {
         Ok(res) => res,
         Err(e) => return Err(warp::reject::custom(e)),
     };
 
-    let content = match content {
-        Ok(res) => res,
-        Err(e) => return Err(warp::reject::custom(e)),
+    let held = profanity.config.question_title.holds(&title)
+        || profanity.config.question_content.holds(&content)
+        || suspect.is_some();
+    let visibility = if held {
+        Visibility::Pending
+    } else {
+        Visibility::Visible
     };
-
+    // Bad words and content accepted unchecked while failing open are reviewed
+    let review_reason = ProfanityCheck::review_reason(&[&title, &content]);
     let question = NewQuestion {
-        title,
-        content,
+        title: profanity.config.question_title.apply(&title)?,
+        content: profanity.config.question_content.apply(&content)?,
         tags: new_question.tags,
     };
 
-    match store.add_question(question, account_id).await {
-        Ok(question) => Ok(warp::reply::json(&question)),
+    // Only posts which passed validation count against the posting rate
+    spam.check_post_rate(&store, &account_id, ip).await?;
+
+    // The question is only stored together with its moderation items, so a failure cannot
+    // leave a stored question behind that the client then posts again
+    let transaction = store.begin().await?;
+    match transaction
+        .add_question(question, account_id.clone(), visibility)
+        .await
+    {
+        Ok(question) => {
+            if let Some(reason) = review_reason {
+                transaction
+                    .enqueue_moderation_item(NewModerationItem {
+                        question_id: Some(question.id.clone()),
+                        answer_id: None,
+                        comment_id: None,
+                        source: ModerationSource::ProfanityFilter,
+                        reason: Some(reason),
+                        reported_by: None,
+                    })
+                    .await?;
+            }
+            if let Some(reason) = suspect {
+                transaction
+                    .enqueue_moderation_item(NewModerationItem {
+                        question_id: Some(question.id.clone()),
+                        answer_id: None,
+                        comment_id: None,
+                        source: ModerationSource::SpamFilter,
+                        reason: Some(reason),
+                        reported_by: None,
+                    })
+                    .await?;
+            }
+            transaction.commit().await?;
+            // Authors follow their own questions to get notified about answers
+            let _ = store.follow_question(question.id.0, &account_id).await;
+            // Held questions are stored, but not published yet
+            let status = if held {
+                StatusCode::ACCEPTED
+            } else {
+                // Failures are logged by the store, but must not fail the request
+                let _ = store
+                    .enqueue_webhook_deliveries(
+                        WebhookEvent::QuestionCreated,
+                        serde_json::json!(question),
+                    )
+                    .await;
+                StatusCode::OK
+            };
+            Ok(warp::reply::with_status(
+                warp::reply::json(&question),
+                status,
+            ))
+        }
         Err(e) => Err(warp::reject::custom(e)),
     }
 }
@@ -104,41 +176,97 @@ pub async fn update_question(
     id: i32,
     session: Session,
     store: store::Store,
+    profanity: Profanity,
+    if_match: Option<String>,
     question: Question,
 ) -> Result<impl Reply, Rejection> {
-    if store
-        .is_question_owner(question.id.0, &session.account_id)
-        .await?
-    {
-        let title = check_profanity(question.title);
-
-        let content = check_profanity(question.content);
+    // Editing someone else's question requires enough reputation
+    if !store.is_question_owner(id, &session.account_id).await? {
+        store
+            .require_privilege(&session.account_id, Privilege::EditOthersPosts)
+            .await?;
+    }
 
-        let (title, content) = tokio::join!(title, content);
+    // Title and content are checked with a single upstream request
+    let [title, content] = match profanity
+        .check_all([question.title, question.content])
+        .await
+    {
+        Ok(res) => res,
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
 
-        let title = match title {
-            Ok(res) => res,
-            Err(e) => return Err(warp::reject::custom(e)),
-        };
+    let held = profanity.config.question_title.holds(&title)
+        || profanity.config.question_content.holds(&content);
+    // Only hide the question again if the edit has to be reviewed
+    let visibility = held.then_some(Visibility::Pending);
+    // Bad words and content accepted unchecked while failing open are reviewed
+    let review_reason = ProfanityCheck::review_reason(&[&title, &content]);
+    let question = Question {
+        id: question.id,
+        title: profanity.config.question_title.apply(&title)?,
+        content: profanity.config.question_content.apply(&content)?,
+        tags: question.tags,
+        author: question.author,
+        closed: question.closed,
+        bookmarked: None,
+        updated_on: None,
+    };
 
-        let content = match content {
-            Ok(res) => res,
+    // The edit is only stored together with its moderation item. With `If-Match` the question
+    // is also locked from the check until the update is committed, so concurrent edits cannot
+    // get lost in between. Rolled back on every early return.
+    let transaction = store.begin().await?;
+    if let Some(if_match) = if_match {
+        // Without a current representation the precondition fails as well
+        let etag = match transaction.lock_question(id, &session.account_id).await {
+            Ok(current) => conditional::etag_of(&current).ok(),
+            Err(handle_error::Error::QuestionNotFound) => None,
             Err(e) => return Err(warp::reject::custom(e)),
         };
+        if !etag.is_some_and(|etag| conditional::if_match(&if_match, &etag)) {
+            return Err(warp::reject::custom(
+                handle_error::Error::PreconditionFailed,
+            ));
+        }
+    }
 
-        let question = Question {
-            id: question.id,
-            title,
-            content,
-            tags: question.tags,
-        };
-
-        match store.update_question(question, id).await {
-            Ok(question) => Ok(warp::reply::json(&question)),
-            Err(e) => Err(warp::reject::custom(e)),
+    match transaction
+        .update_question(question, id, visibility, &session.account_id)
+        .await
+    {
+        Ok(question) => {
+            if let Some(reason) = review_reason {
+                transaction
+                    .enqueue_moderation_item(NewModerationItem {
+                        question_id: Some(question.id.clone()),
+                        answer_id: None,
+                        comment_id: None,
+                        source: ModerationSource::ProfanityFilter,
+                        reason: Some(reason),
+                        reported_by: None,
+                    })
+                    .await?;
+            }
+            transaction.commit().await?;
+            // Held questions are stored, but not published yet
+            let status = if held {
+                StatusCode::ACCEPTED
+            } else {
+                StatusCode::OK
+            };
+            let reply = warp::reply::with_status(warp::reply::json(&question), status);
+            // The new entity tag allows further edits without fetching the question again
+            match conditional::etag_of(&question) {
+                Ok(etag) if !held => Ok(conditional::with_validators(
+                    reply,
+                    &etag,
+                    question.updated_on,
+                )),
+                _ => Ok(reply.into_response()),
+            }
         }
-    } else {
-        Err(warp::reject::custom(handle_error::Error::Unauthorized))
+        Err(e) => Err(warp::reject::custom(e)),
     }
 }
 
@@ -148,13 +276,85 @@ pub async fn delete_question(
     store: store::Store,
 ) -> Result<impl Reply, Rejection> {
     let account_id = session.account_id;
-    if store.is_question_owner(id, &account_id).await? {
-        if let Err(e) = store.delete_question(id, account_id).await {
-            return Err(warp::reject::custom(e));
-        }
+    // Rolled back on every early return
+    let transaction = store.begin().await?;
+    if transaction.is_question_owner(id, &account_id).await? {
+        // Answers are deleted with the question, the reputation earned with them is not
+        let affected = transaction.remove_question_reputation_events(id).await?;
+        transaction.delete_question(id, account_id).await?;
+        jobs::enqueue(
+            &transaction,
+            RecomputeReputation {
+                account_ids: affected,
+            },
+        )
+        .await?;
+        transaction.commit().await?;
         Ok(warp::reply::with_status("Question deleted", StatusCode::OK))
     } else {
-        println!("{}", id);
+        event!(
+            Level::WARN,
+            "Account {} may not delete question {}",
+            account_id.0,
+            id
+        );
         Err(warp::reject::custom(handle_error::Error::Unauthorized))
     }
 }
+
+pub async fn close_question(
+    id: i32,
+    session: Session,
+    store: store::Store,
+    close: CloseQuestion,
+) -> Result<impl Reply, Rejection> {
+    store
+        .require_privilege(&session.account_id, Privilege::CloseQuestions)
+        .await?;
+
+    match (close.reason, &close.duplicate_of) {
+        (CloseReason::Duplicate, Some(duplicate_of)) if duplicate_of.0 != id => {
+            // The canonical question has to exist
+            store
+                .get_question_author(duplicate_of.0)
+                .await
+                .map_err(|_| handle_error::Error::InvalidDuplicate)?;
+        }
+        (CloseReason::Duplicate, _) => {
+            return Err(warp::reject::custom(handle_error::Error::InvalidDuplicate))
+        }
+        (_, _) => {}
+    }
+
+    let close = CloseQuestion {
+        // Only duplicates link to another question
+        duplicate_of: match close.reason {
+            CloseReason::Duplicate => close.duplicate_of,
+            _ => None,
+        },
+        ..close
+    };
+
+    match store.close_question(id, close, &session.account_id).await {
+        Ok(_) => Ok(warp::reply::with_status("Question closed", StatusCode::OK)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn reopen_question(
+    id: i32,
+    session: Session,
+    store: store::Store,
+) -> Result<impl Reply, Rejection> {
+    store
+        .require_privilege(&session.account_id, Privilege::CloseQuestions)
+        .await?;
+
+    match store.reopen_question(id).await {
+        Ok(_) => Ok(warp::reply::with_status(
+            "Question reopened",
+            StatusCode::OK,
+        )),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
@@ -0,0 +1,37 @@
+use crate::store;
+use crate::types::account::{AccountId, Session, UpdateProfile, UserDetails};
+use tracing::instrument;
+use warp::{Rejection, Reply};
+
+#[instrument]
+pub async fn get_user(
+    id: i32,
+    session: Option<Session>,
+    store: store::Store,
+) -> Result<impl Reply, Rejection> {
+    let viewer = session.map(|session| session.account_id);
+    let (profile, questions, answers) = tokio::join!(
+        store.get_profile(AccountId(id), viewer.as_ref()),
+        store.get_questions_by_account(AccountId(id)),
+        store.get_answers_by_account(AccountId(id)),
+    );
+
+    let user = UserDetails {
+        profile: profile?,
+        questions: questions?,
+        answers: answers?,
+    };
+
+    Ok(warp::reply::json(&user))
+}
+
+pub async fn update_profile(
+    session: Session,
+    store: store::Store,
+    profile: UpdateProfile,
+) -> Result<impl Reply, Rejection> {
+    match store.update_profile(session.account_id, profile).await {
+        Ok(profile) => Ok(warp::reply::json(&profile)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
@@ -0,0 +1,114 @@
+use crate::jobs::{self, RecomputeReputation};
+use crate::store;
+use crate::types::account::Session;
+use crate::types::reputation::{Privilege, ReputationEvent, ReputationEventKind};
+use crate::types::vote::{Vote, VoteDirection};
+use warp::{http::StatusCode, Rejection, Reply};
+
+pub async fn vote_question(
+    id: i32,
+    session: Session,
+    store: store::Store,
+    vote: Vote,
+) -> Result<impl Reply, Rejection> {
+    store.require_visible_question(id).await?;
+    let author = store.get_question_author(id).await?;
+    if author == session.account_id {
+        return Err(warp::reject::custom(handle_error::Error::CannotVoteOwnPost));
+    }
+
+    if vote.direction == VoteDirection::Down {
+        store
+            .require_privilege(&session.account_id, Privilege::VoteDown)
+            .await?;
+    }
+
+    // The vote, the reputation ledger and the queued recomputation change together
+    let transaction = store.begin().await?;
+    transaction
+        .vote_question(id, &session.account_id, vote.direction)
+        .await?;
+
+    let event = ReputationEvent::new(
+        vote.direction.question_event_kind(),
+        author,
+        session.account_id,
+        Some(id),
+        None,
+    );
+    let affected = transaction
+        .replace_reputation_event(
+            event,
+            &[
+                ReputationEventKind::QuestionUpvoted,
+                ReputationEventKind::QuestionDownvoted,
+            ],
+        )
+        .await?;
+    jobs::enqueue(
+        &transaction,
+        RecomputeReputation {
+            account_ids: affected,
+        },
+    )
+    .await?;
+    transaction.commit().await?;
+
+    Ok(warp::reply::with_status("Vote recorded", StatusCode::OK))
+}
+
+pub async fn vote_answer(
+    id: i32,
+    session: Session,
+    store: store::Store,
+    vote: Vote,
+) -> Result<impl Reply, Rejection> {
+    store.require_visible_answer(id).await?;
+    let answer = store.get_answer(id).await?;
+    let author = answer
+        .author
+        .map(|author| author.id)
+        .ok_or(handle_error::Error::AnswerNotFound)?;
+    if author == session.account_id {
+        return Err(warp::reject::custom(handle_error::Error::CannotVoteOwnPost));
+    }
+
+    if vote.direction == VoteDirection::Down {
+        store
+            .require_privilege(&session.account_id, Privilege::VoteDown)
+            .await?;
+    }
+
+    // The vote, the reputation ledger and the queued recomputation change together
+    let transaction = store.begin().await?;
+    transaction
+        .vote_answer(id, &session.account_id, vote.direction)
+        .await?;
+
+    let event = ReputationEvent::new(
+        vote.direction.answer_event_kind(),
+        author,
+        session.account_id,
+        None,
+        Some(id),
+    );
+    let affected = transaction
+        .replace_reputation_event(
+            event,
+            &[
+                ReputationEventKind::AnswerUpvoted,
+                ReputationEventKind::AnswerDownvoted,
+            ],
+        )
+        .await?;
+    jobs::enqueue(
+        &transaction,
+        RecomputeReputation {
+            account_ids: affected,
+        },
+    )
+    .await?;
+    transaction.commit().await?;
+
+    Ok(warp::reply::with_status("Vote recorded", StatusCode::OK))
+}
@@ -0,0 +1,97 @@
+use crate::store;
+use crate::types::account::Session;
+use crate::types::pagination::{extract_pagination, Pagination};
+use crate::types::webhook::NewWebhook;
+use crate::webhook::{self, generate_secret};
+use std::collections::HashMap;
+use warp::{http::StatusCode, Rejection, Reply};
+
+/// Registers a webhook. The response contains the secret the payloads are signed with,
+/// it cannot be requested again.
+pub async fn add_webhook(
+    session: Session,
+    store: store::Store,
+    new_webhook: NewWebhook,
+) -> Result<impl Reply, Rejection> {
+    if new_webhook.events.is_empty() || !webhook::is_allowed_url(&new_webhook.url).await {
+        return Err(warp::reject::custom(handle_error::Error::InvalidWebhook));
+    }
+
+    match store
+        .add_webhook(new_webhook, generate_secret(), &session.account_id)
+        .await
+    {
+        Ok(webhook) => Ok(warp::reply::with_status(
+            warp::reply::json(&webhook),
+            StatusCode::CREATED,
+        )),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn get_webhooks(session: Session, store: store::Store) -> Result<impl Reply, Rejection> {
+    match store.get_webhooks(&session.account_id).await {
+        Ok(webhooks) => Ok(warp::reply::json(&webhooks)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn delete_webhook(
+    id: i32,
+    session: Session,
+    store: store::Store,
+) -> Result<impl Reply, Rejection> {
+    match store.delete_webhook(id, &session.account_id).await {
+        Ok(_) => Ok(warp::reply::with_status("Webhook deleted", StatusCode::OK)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Lists deliveries of a webhook with the outcome of their last attempt, newest first
+/// # Example query
+/// `/webhooks/1/deliveries?limit=10&offset=0`
+pub async fn get_webhook_deliveries(
+    id: i32,
+    params: HashMap<String, String>,
+    session: Session,
+    store: store::Store,
+) -> Result<impl Reply, Rejection> {
+    // Webhooks of other accounts are reported as not found
+    if !store.is_webhook_owner(id, &session.account_id).await? {
+        return Err(warp::reject::custom(handle_error::Error::WebhookNotFound));
+    }
+
+    let mut pagination = Pagination::default();
+
+    if !params.is_empty() {
+        pagination = extract_pagination(params)?;
+    }
+
+    match store
+        .get_webhook_deliveries(id, pagination.limit, pagination.offset)
+        .await
+    {
+        Ok(deliveries) => Ok(warp::reply::json(&deliveries)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Attempts a failed or dead-lettered delivery again
+pub async fn retry_webhook_delivery(
+    id: i32,
+    delivery_id: i32,
+    session: Session,
+    store: store::Store,
+) -> Result<impl Reply, Rejection> {
+    if !store.is_webhook_owner(id, &session.account_id).await? {
+        return Err(warp::reject::custom(handle_error::Error::WebhookNotFound));
+    }
+
+    match store.retry_webhook_delivery(id, delivery_id).await {
+        Ok(_) => Ok(warp::reply::with_status(
+            "Webhook delivery queued",
+            StatusCode::ACCEPTED,
+        )),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
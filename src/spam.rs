@@ -0,0 +1,291 @@
+use chrono::{Duration as ChronoDuration, Utc};
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use warp::Filter;
+
+use crate::config::SpamConfig;
+use crate::store::Store;
+use crate::types::account::AccountId;
+
+/// Number of tracked keys after which keys without recent attempts are dropped
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Recent attempts of a key and the window they are counted in
+#[derive(Debug)]
+struct Attempts {
+    window: Duration,
+    times: VecDeque<Instant>,
+}
+
+/// Sliding window counter of attempts per key, e.g. per account or per IP address
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    attempts: Mutex<HashMap<String, Attempts>>,
+}
+
+impl RateLimiter {
+    /// Records an attempt for every key, unless one of them already had its limit of attempts
+    /// within `window`. Then nothing is recorded and the exceeded limit is returned together
+    /// with how long to wait before the next attempt is allowed.
+    pub fn try_acquire_all(
+        &self,
+        limits: &[(String, u32)],
+        window: Duration,
+    ) -> Result<(), (u32, Duration)> {
+        let mut attempts = self.attempts.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+
+        // Keys are dropped once their last attempt left their own window, which differs
+        // between e.g. posts and registrations
+        if attempts.len() > PRUNE_THRESHOLD {
+            attempts.retain(|_, attempts| {
+                attempts
+                    .times
+                    .back()
+                    .is_some_and(|time| now.duration_since(*time) < attempts.window)
+            });
+        }
+
+        // All limits are checked before any attempt is recorded, so an attempt refused for
+        // one key does not count against the others
+        for (key, limit) in limits {
+            let attempts = attempts.entry(key.clone()).or_insert_with(|| Attempts {
+                window,
+                times: VecDeque::new(),
+            });
+            attempts.window = window;
+            let times = &mut attempts.times;
+            while let Some(oldest) = times.front() {
+                if now.duration_since(*oldest) >= window {
+                    times.pop_front();
+                } else {
+                    break;
+                }
+            }
+
+            // A limit of 0 refuses every attempt for the whole window
+            if times.len() as u32 >= *limit {
+                let retry_after = match times.front() {
+                    Some(oldest) => window.saturating_sub(now.duration_since(*oldest)),
+                    None => window,
+                };
+                return Err((*limit, retry_after));
+            }
+        }
+
+        for (key, _) in limits {
+            if let Some(attempts) = attempts.get_mut(key) {
+                attempts.times.push_back(now);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Posting rate limits and heuristics which hold suspect posts for review
+#[derive(Debug, Clone)]
+pub struct Spam {
+    pub config: SpamConfig,
+    limiter: Arc<RateLimiter>,
+}
+
+impl Spam {
+    pub fn new(config: SpamConfig) -> Self {
+        Spam {
+            config,
+            limiter: Arc::new(RateLimiter::default()),
+        }
+    }
+
+    /// Fails with `Error::TooManyRequests` if the account or the IP address posted too often,
+    /// otherwise counts the post. Accounts younger than `new_account_age_hours` get a lower
+    /// limit. Called once the post was validated, so refused posts do not count.
+    pub async fn check_post_rate(
+        &self,
+        store: &Store,
+        account_id: &AccountId,
+        ip: Option<IpAddr>,
+    ) -> Result<(), handle_error::Error> {
+        let window = Duration::from_secs(self.config.post_window_secs);
+        let limit = if self.is_new_account(store, account_id).await? {
+            self.config.new_account_posts
+        } else {
+            self.config.posts_per_account
+        };
+
+        let mut limits = vec![(format!("post:account:{}", account_id.0), limit)];
+        if let Some(ip) = ip {
+            limits.push((format!("post:ip:{}", ip), self.config.posts_per_ip));
+        }
+
+        self.acquire(&limits, window)
+    }
+
+    pub fn check_registration_rate(&self, ip: Option<IpAddr>) -> Result<(), handle_error::Error> {
+        match ip {
+            Some(ip) => self.acquire(
+                &[(
+                    format!("registration:ip:{}", ip),
+                    self.config.registrations_per_ip,
+                )],
+                Duration::from_secs(self.config.registration_window_secs),
+            ),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns why a post looks like spam and has to be reviewed before it is published,
+    /// or `None` if it can be published right away
+    pub async fn assess(
+        &self,
+        store: &Store,
+        account_id: &AccountId,
+        content: &str,
+    ) -> Result<Option<String>, handle_error::Error> {
+        let words = content.split_whitespace().count();
+        let links = content
+            .split_whitespace()
+            .filter(|word| word.contains("http://") || word.contains("https://"))
+            .count();
+
+        if links > self.config.max_links
+            || (links > 0 && links as f64 / words as f64 > self.config.max_link_ratio)
+        {
+            return Ok(Some(format!("Too many links ({})", links)));
+        }
+
+        if store
+            .is_recent_duplicate(content, self.config.duplicate_window_secs)
+            .await?
+        {
+            return Ok(Some("Duplicate of a recent post".to_string()));
+        }
+
+        if links > 0 && self.is_new_account(store, account_id).await? {
+            return Ok(Some("Links posted by a new account".to_string()));
+        }
+
+        Ok(None)
+    }
+
+    async fn is_new_account(
+        &self,
+        store: &Store,
+        account_id: &AccountId,
+    ) -> Result<bool, handle_error::Error> {
+        let profile = store
+            .get_profile(account_id.clone(), Some(account_id))
+            .await?;
+        let age = Utc::now().naive_utc() - profile.created_on;
+
+        Ok(age < ChronoDuration::hours(self.config.new_account_age_hours))
+    }
+
+    fn acquire(
+        &self,
+        limits: &[(String, u32)],
+        window: Duration,
+    ) -> Result<(), handle_error::Error> {
+        self.limiter
+            .try_acquire_all(limits, window)
+            .map_err(|(limit, retry_after)| {
+                // Round up, a client retrying after 0 seconds would be refused again
+                handle_error::Error::TooManyRequests(handle_error::RateLimited {
+                    limit,
+                    retry_after: retry_after.as_secs() + 1,
+                    reset: window.as_secs(),
+                })
+            })
+    }
+}
+
+/// IP address of the client. Proxy headers are not trusted, so behind a reverse proxy this is
+/// the address of the proxy.
+pub fn client_ip(
+) -> impl Filter<Extract = (Option<IpAddr>,), Error = std::convert::Infallible> + Clone {
+    warp::addr::remote().map(|addr: Option<std::net::SocketAddr>| addr.map(|addr| addr.ip()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn acquire(
+        limiter: &RateLimiter,
+        key: &str,
+        limit: u32,
+        window: Duration,
+    ) -> Result<(), Duration> {
+        limiter
+            .try_acquire_all(&[(key.to_string(), limit)], window)
+            .map_err(|(_, retry_after)| retry_after)
+    }
+
+    #[test]
+    fn refuses_attempts_over_the_limit_within_the_window() {
+        let limiter = RateLimiter::default();
+        let window = Duration::from_secs(60);
+        assert!(acquire(&limiter, "a", 2, window).is_ok());
+        assert!(acquire(&limiter, "a", 2, window).is_ok());
+
+        let retry_after = acquire(&limiter, "a", 2, window).unwrap_err();
+        assert!(retry_after <= window && retry_after > Duration::from_secs(59));
+        // Other keys are counted on their own
+        assert!(acquire(&limiter, "b", 2, window).is_ok());
+    }
+
+    #[test]
+    fn allows_attempts_again_after_the_window() {
+        let limiter = RateLimiter::default();
+        let window = Duration::from_millis(20);
+        assert!(acquire(&limiter, "a", 1, window).is_ok());
+        assert!(acquire(&limiter, "a", 1, window).is_err());
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(acquire(&limiter, "a", 1, window).is_ok());
+    }
+
+    #[test]
+    fn limit_of_zero_refuses_every_attempt() {
+        let limiter = RateLimiter::default();
+        let window = Duration::from_secs(60);
+        assert_eq!(acquire(&limiter, "a", 0, window), Err(window));
+        assert_eq!(acquire(&limiter, "a", 0, window), Err(window));
+    }
+
+    #[test]
+    fn refused_attempt_counts_against_no_key() {
+        let limiter = RateLimiter::default();
+        let window = Duration::from_secs(60);
+        assert!(acquire(&limiter, "ip", 1, window).is_ok());
+
+        let limits = [("account".to_string(), 1), ("ip".to_string(), 1)];
+        let (limit, _) = limiter.try_acquire_all(&limits, window).unwrap_err();
+        assert_eq!(limit, 1);
+        // The account's attempt was not recorded although its own limit was not reached
+        assert!(acquire(&limiter, "account", 1, window).is_ok());
+    }
+
+    #[test]
+    fn prune_keeps_keys_within_their_own_window() {
+        let limiter = RateLimiter::default();
+        let long = Duration::from_secs(3600);
+        assert!(acquire(&limiter, "registration", 1, long).is_ok());
+
+        std::thread::sleep(Duration::from_millis(30));
+        // Attempts with a shorter window fill the map until it is pruned
+        let short = Duration::from_millis(10);
+        for i in 0..=PRUNE_THRESHOLD {
+            assert!(acquire(&limiter, &format!("post:{}", i), 1, short).is_ok());
+        }
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(acquire(&limiter, "post", 1, short).is_ok());
+
+        let attempts = limiter.attempts.lock().unwrap();
+        assert!(attempts.len() < PRUNE_THRESHOLD);
+        drop(attempts);
+        assert!(acquire(&limiter, "registration", 1, long).is_err());
+    }
+}
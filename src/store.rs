@@ -1,52 +1,739 @@
-use sqlx::postgres::{PgPool, PgPoolOptions, PgRow};
-use sqlx::Row;
+use chrono::NaiveDateTime;
+use serde::Deserialize;
+use sqlx::pool::PoolConnection;
+use sqlx::postgres::{PgConnectOptions, PgConnection, PgPool, PgPoolOptions, PgSslMode, Postgres};
+use sqlx::Transaction;
+use std::ops::{Deref, DerefMut};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{watch, MappedMutexGuard, Mutex, MutexGuard};
+use tokio::task::JoinHandle;
 
-use crate::types::account::{Account, AccountId};
+use crate::config::DatabaseConfig;
+use crate::metrics;
+use crate::types::account::{Account, AccountId, Author, Profile, UpdateProfile};
 use crate::types::answer::{Answer, AnswerId, NewAnswer};
-use crate::types::question::{NewQuestion, Question, QuestionId};
+use crate::types::comment::{Comment, CommentId, NewComment};
+use crate::types::feed::{FeedEvent, FeedEventKind, FeedNotification, FEED_CHANNEL};
+use crate::types::job::{ClaimedJob, JobId, JobStatus};
+use crate::types::moderation::{
+    ModerationAction, ModerationItem, ModerationItemId, ModerationSource, ModerationStatus,
+    NewModerationAction, NewModerationItem, Visibility,
+};
+use crate::types::notification::{Notification, NotificationId, NotificationKind};
+use crate::types::question::{
+    CloseQuestion, CloseReason, NewQuestion, Question, QuestionClosure, QuestionId,
+};
+use crate::types::reputation::{Privilege, ReputationEvent, ReputationEventKind};
+use crate::types::vote::VoteDirection;
+use crate::types::webhook::{
+    DeliveryStatus, NewWebhook, PendingDelivery, Webhook, WebhookDelivery, WebhookDeliveryId,
+    WebhookEvent, WebhookId,
+};
 use handle_error::Error;
 
+/// `sslmode` of the database connection, see
+/// https://www.postgresql.org/docs/current/libpq-ssl.html#LIBPQ-SSL-PROTECTION
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DatabaseSslMode {
+    Disable,
+    Allow,
+    Prefer,
+    Require,
+    VerifyCa,
+    VerifyFull,
+}
+
+impl DatabaseSslMode {
+    fn pg_ssl_mode(&self) -> PgSslMode {
+        match self {
+            DatabaseSslMode::Disable => PgSslMode::Disable,
+            DatabaseSslMode::Allow => PgSslMode::Allow,
+            DatabaseSslMode::Prefer => PgSslMode::Prefer,
+            DatabaseSslMode::Require => PgSslMode::Require,
+            DatabaseSslMode::VerifyCa => PgSslMode::VerifyCa,
+            DatabaseSslMode::VerifyFull => PgSslMode::VerifyFull,
+        }
+    }
+}
+
+/// Read replica, taken out of rotation while it is unreachable or lagging behind
+#[derive(Debug)]
+struct Replica {
+    pool: PgPool,
+    healthy: AtomicBool,
+}
+
+/// Connection a query runs on, taken from a pool or the transaction of the store
+enum Connection<'a> {
+    Pooled(Box<PoolConnection<Postgres>>),
+    Transaction(MappedMutexGuard<'a, Transaction<'static, Postgres>>),
+}
+
+impl Deref for Connection<'_> {
+    type Target = PgConnection;
+
+    fn deref(&self) -> &PgConnection {
+        match self {
+            Connection::Pooled(connection) => connection,
+            Connection::Transaction(transaction) => transaction,
+        }
+    }
+}
+
+impl DerefMut for Connection<'_> {
+    fn deref_mut(&mut self) -> &mut PgConnection {
+        match self {
+            Connection::Pooled(connection) => connection,
+            Connection::Transaction(transaction) => transaction,
+        }
+    }
+}
+
+// Rows as returned by `sqlx::query_as!`. Their columns are checked against the schema at
+// compile time, the live database or the query metadata in `.sqlx/` tell their types.
+
+fn decode_error(e: impl Into<sqlx::error::BoxDynError>) -> sqlx::Error {
+    sqlx::Error::Decode(e.into())
+}
+
+/// A question with its author, `bookmarked` is `NULL` unless the viewer is known
+struct QuestionRow {
+    id: i32,
+    title: String,
+    content: String,
+    tags: Vec<String>,
+    account_id: i32,
+    close_reason: Option<String>,
+    close_message: Option<String>,
+    duplicate_of: Option<i32>,
+    closed_on: Option<NaiveDateTime>,
+    display_name: Option<String>,
+    avatar_url: Option<String>,
+    bookmarked: Option<bool>,
+    updated_on: NaiveDateTime,
+}
+
+impl TryFrom<QuestionRow> for Question {
+    type Error = sqlx::Error;
+
+    fn try_from(row: QuestionRow) -> Result<Self, Self::Error> {
+        let closed = match (row.close_reason, row.closed_on) {
+            (Some(reason), Some(closed_on)) => Some(QuestionClosure {
+                reason: reason.parse::<CloseReason>().map_err(decode_error)?,
+                message: row.close_message,
+                duplicate_of: row.duplicate_of.map(QuestionId),
+                closed_on,
+            }),
+            _ => None,
+        };
+
+        Ok(Question {
+            id: QuestionId(row.id),
+            title: row.title,
+            content: row.content,
+            tags: Some(row.tags),
+            author: Some(Author {
+                id: AccountId(row.account_id),
+                display_name: row.display_name,
+                avatar_url: row.avatar_url,
+            }),
+            closed,
+            bookmarked: row.bookmarked,
+            updated_on: Some(row.updated_on),
+        })
+    }
+}
+
+/// An answer with its author
+struct AnswerRow {
+    id: i32,
+    content: String,
+    corresponding_question: i32,
+    account_id: i32,
+    display_name: Option<String>,
+    avatar_url: Option<String>,
+}
+
+impl From<AnswerRow> for Answer {
+    fn from(row: AnswerRow) -> Self {
+        Answer {
+            id: AnswerId(row.id),
+            content: row.content,
+            question_id: QuestionId(row.corresponding_question),
+            author: Some(Author {
+                id: AccountId(row.account_id),
+                display_name: row.display_name,
+                avatar_url: row.avatar_url,
+            }),
+        }
+    }
+}
+
+/// A comment with its author
+struct CommentRow {
+    id: i32,
+    content: String,
+    question_id: i32,
+    account_id: i32,
+    display_name: Option<String>,
+    avatar_url: Option<String>,
+}
+
+impl From<CommentRow> for Comment {
+    fn from(row: CommentRow) -> Self {
+        Comment {
+            id: CommentId(row.id),
+            content: row.content,
+            question_id: QuestionId(row.question_id),
+            author: Some(Author {
+                id: AccountId(row.account_id),
+                display_name: row.display_name,
+                avatar_url: row.avatar_url,
+            }),
+        }
+    }
+}
+
+struct AccountRow {
+    id: i32,
+    email: String,
+    password: String,
+}
+
+impl From<AccountRow> for Account {
+    fn from(row: AccountRow) -> Self {
+        Account {
+            id: Some(AccountId(row.id)),
+            email: row.email,
+            password: row.password,
+        }
+    }
+}
+
+struct ProfileRow {
+    id: i32,
+    display_name: Option<String>,
+    bio: Option<String>,
+    avatar_url: Option<String>,
+    reputation: i32,
+    created_on: NaiveDateTime,
+}
+
+impl From<ProfileRow> for Profile {
+    fn from(row: ProfileRow) -> Self {
+        Profile {
+            id: AccountId(row.id),
+            display_name: row.display_name,
+            bio: row.bio,
+            avatar_url: row.avatar_url,
+            reputation: row.reputation,
+            created_on: row.created_on,
+        }
+    }
+}
+
+struct NotificationRow {
+    id: i32,
+    kind: String,
+    question_id: i32,
+    answer_id: Option<i32>,
+    comment_id: Option<i32>,
+    read: bool,
+    created_on: NaiveDateTime,
+}
+
+impl TryFrom<NotificationRow> for Notification {
+    type Error = sqlx::Error;
+
+    fn try_from(row: NotificationRow) -> Result<Self, Self::Error> {
+        Ok(Notification {
+            id: NotificationId(row.id),
+            kind: row.kind.parse::<NotificationKind>().map_err(decode_error)?,
+            question_id: QuestionId(row.question_id),
+            answer_id: row.answer_id.map(AnswerId),
+            comment_id: row.comment_id.map(CommentId),
+            read: row.read,
+            created_on: row.created_on,
+        })
+    }
+}
+
+struct ModerationItemRow {
+    id: i32,
+    question_id: Option<i32>,
+    answer_id: Option<i32>,
+    comment_id: Option<i32>,
+    source: String,
+    reason: Option<String>,
+    reported_by: Option<i32>,
+    status: String,
+    created_on: NaiveDateTime,
+}
+
+impl TryFrom<ModerationItemRow> for ModerationItem {
+    type Error = sqlx::Error;
+
+    fn try_from(row: ModerationItemRow) -> Result<Self, Self::Error> {
+        Ok(ModerationItem {
+            id: ModerationItemId(row.id),
+            question_id: row.question_id.map(QuestionId),
+            answer_id: row.answer_id.map(AnswerId),
+            comment_id: row.comment_id.map(CommentId),
+            source: row
+                .source
+                .parse::<ModerationSource>()
+                .map_err(decode_error)?,
+            reason: row.reason,
+            reported_by: row.reported_by.map(AccountId),
+            status: row
+                .status
+                .parse::<ModerationStatus>()
+                .map_err(decode_error)?,
+            created_on: row.created_on,
+        })
+    }
+}
+
+struct ModerationActionRow {
+    item_id: i32,
+    moderator_account_id: i32,
+    action: String,
+    note: Option<String>,
+    previous_title: Option<String>,
+    previous_content: Option<String>,
+    created_on: NaiveDateTime,
+}
+
+impl TryFrom<ModerationActionRow> for ModerationAction {
+    type Error = sqlx::Error;
+
+    fn try_from(row: ModerationActionRow) -> Result<Self, Self::Error> {
+        Ok(ModerationAction {
+            item_id: ModerationItemId(row.item_id),
+            moderator_account_id: AccountId(row.moderator_account_id),
+            action: row
+                .action
+                .parse::<ModerationStatus>()
+                .map_err(decode_error)?,
+            note: row.note,
+            previous_title: row.previous_title,
+            previous_content: row.previous_content,
+            created_on: row.created_on,
+        })
+    }
+}
+
+/// A webhook, `secret` is `NULL` unless it was just created
+struct WebhookRow {
+    id: i32,
+    url: String,
+    secret: Option<String>,
+    events: Vec<String>,
+    created_on: NaiveDateTime,
+}
+
+impl TryFrom<WebhookRow> for Webhook {
+    type Error = sqlx::Error;
+
+    fn try_from(row: WebhookRow) -> Result<Self, Self::Error> {
+        Ok(Webhook {
+            id: WebhookId(row.id),
+            url: row.url,
+            events: row
+                .events
+                .iter()
+                .map(|event| event.parse::<WebhookEvent>())
+                .collect::<Result<_, _>>()
+                .map_err(decode_error)?,
+            secret: row.secret,
+            created_on: row.created_on,
+        })
+    }
+}
+
+struct WebhookDeliveryRow {
+    id: i32,
+    webhook_id: i32,
+    event: String,
+    payload: serde_json::Value,
+    status: String,
+    attempts: i32,
+    last_status_code: Option<i32>,
+    last_error: Option<String>,
+    next_attempt_on: Option<NaiveDateTime>,
+    created_on: NaiveDateTime,
+    delivered_on: Option<NaiveDateTime>,
+}
+
+impl TryFrom<WebhookDeliveryRow> for WebhookDelivery {
+    type Error = sqlx::Error;
+
+    fn try_from(row: WebhookDeliveryRow) -> Result<Self, Self::Error> {
+        Ok(WebhookDelivery {
+            id: WebhookDeliveryId(row.id),
+            webhook_id: WebhookId(row.webhook_id),
+            event: row.event.parse::<WebhookEvent>().map_err(decode_error)?,
+            payload: row.payload,
+            status: row.status.parse::<DeliveryStatus>().map_err(decode_error)?,
+            attempts: row.attempts,
+            last_status_code: row.last_status_code,
+            last_error: row.last_error,
+            next_attempt_on: row.next_attempt_on,
+            created_on: row.created_on,
+            delivered_on: row.delivered_on,
+        })
+    }
+}
+
+/// A claimed delivery with the webhook it goes to
+struct PendingDeliveryRow {
+    id: i32,
+    event: String,
+    payload: serde_json::Value,
+    attempts: i32,
+    url: String,
+    secret: String,
+    created_on: NaiveDateTime,
+}
+
+impl TryFrom<PendingDeliveryRow> for PendingDelivery {
+    type Error = sqlx::Error;
+
+    fn try_from(row: PendingDeliveryRow) -> Result<Self, Self::Error> {
+        Ok(PendingDelivery {
+            id: WebhookDeliveryId(row.id),
+            event: row.event.parse::<WebhookEvent>().map_err(decode_error)?,
+            payload: row.payload,
+            attempts: row.attempts,
+            url: row.url,
+            secret: row.secret,
+            created_on: row.created_on,
+        })
+    }
+}
+
+struct ClaimedJobRow {
+    id: i64,
+    kind: String,
+    payload: serde_json::Value,
+    attempts: i32,
+    max_attempts: Option<i32>,
+    recurring_key: Option<String>,
+}
+
+impl From<ClaimedJobRow> for ClaimedJob {
+    fn from(row: ClaimedJobRow) -> Self {
+        ClaimedJob {
+            id: JobId(row.id),
+            kind: row.kind,
+            payload: row.payload,
+            attempts: row.attempts,
+            max_attempts: row.max_attempts,
+            recurring_key: row.recurring_key,
+        }
+    }
+}
+
+/// Writes, and reads which have to see the latest writes, go to the primary (`connection`).
+/// Public listings are read from healthy replicas in turn, or from the primary without any.
+///
+/// A store returned by `begin` runs all operations in one transaction instead.
 #[derive(Debug, Clone)]
 pub struct Store {
     pub connection: PgPool,
+    /// `None` once committed
+    transaction: Option<Arc<Mutex<Option<Transaction<'static, Postgres>>>>>,
+    replicas: Arc<Vec<Replica>>,
+    next_replica: Arc<AtomicUsize>,
 }
 
 impl Store {
-    pub async fn new(db_url: &str) -> Self {
-        let db_pool = match PgPoolOptions::new()
-            .max_connections(5)
-            .connect(db_url)
-            .await
-        {
-            Ok(pool) => pool,
-            Err(e) => panic!("Error connecting to database: {}", e),
+    /// Connects to the primary, retrying with exponential backoff while it is unreachable.
+    /// Replicas are connected lazily and only used once `monitor_replicas` found them healthy.
+    pub async fn new(
+        db_url: &str,
+        replica_urls: &[String],
+        config: &DatabaseConfig,
+    ) -> Result<Self, sqlx::Error> {
+        let pool_options = PgPoolOptions::new()
+            .max_connections(config.max_connections)
+            .min_connections(config.min_connections)
+            .acquire_timeout(Duration::from_secs(config.acquire_timeout_secs))
+            .idle_timeout(
+                Some(Duration::from_secs(config.idle_timeout_secs))
+                    .filter(|timeout| !timeout.is_zero()),
+            );
+
+        let replicas = replica_urls
+            .iter()
+            .map(|url| {
+                Ok(Replica {
+                    pool: pool_options
+                        .clone()
+                        .connect_lazy_with(Self::connect_options(url, config)?),
+                    healthy: AtomicBool::new(false),
+                })
+            })
+            .collect::<Result<Vec<_>, sqlx::Error>>()?;
+
+        let connect_options = Self::connect_options(db_url, config)?;
+        let mut attempt = 0;
+        loop {
+            match pool_options
+                .clone()
+                .connect_with(connect_options.clone())
+                .await
+            {
+                Ok(db_pool) => {
+                    return Ok(Store {
+                        connection: db_pool,
+                        transaction: None,
+                        replicas: Arc::new(replicas),
+                        next_replica: Arc::new(AtomicUsize::new(0)),
+                    })
+                }
+                Err(e) if attempt < config.connect_retries => {
+                    let backoff_ms = config
+                        .connect_backoff_base_ms
+                        .saturating_mul(2u64.saturating_pow(attempt))
+                        .min(config.connect_backoff_max_ms);
+                    attempt += 1;
+                    tracing::event!(
+                        tracing::Level::WARN,
+                        "Cannot connect to database, retrying in {}ms ({}/{}): {}",
+                        backoff_ms,
+                        attempt,
+                        config.connect_retries,
+                        e
+                    );
+                    tokio::time::sleep(Duration::from_millis(backoff_ms)).await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn connect_options(
+        url: &str,
+        config: &DatabaseConfig,
+    ) -> Result<PgConnectOptions, sqlx::Error> {
+        let mut connect_options =
+            PgConnectOptions::from_str(url)?.application_name(&config.application_name);
+        if let Some(ssl_mode) = config.ssl_mode {
+            connect_options = connect_options.ssl_mode(ssl_mode.pg_ssl_mode());
+        }
+        if config.statement_timeout_ms > 0 {
+            connect_options = connect_options.options([(
+                "statement_timeout",
+                format!("{}ms", config.statement_timeout_ms),
+            )]);
+        }
+        Ok(connect_options)
+    }
+
+    /// Pool for reads which may lag behind the latest writes. Picks the healthy replicas in
+    /// turn and falls back to the primary if there is none.
+    fn reader(&self) -> &PgPool {
+        let count = self.replicas.len();
+        if count == 0 {
+            return &self.connection;
+        }
+        let start = self.next_replica.fetch_add(1, Ordering::Relaxed);
+        (0..count)
+            .map(|offset| &self.replicas[(start + offset) % count])
+            .find(|replica| replica.healthy.load(Ordering::Relaxed))
+            .map_or(&self.connection, |replica| &replica.pool)
+    }
+
+    /// Connection to the primary, or the transaction of the store
+    async fn acquire(&self) -> Result<Connection<'_>, Error> {
+        Box::pin(self.acquire_from(&self.connection)).await
+    }
+
+    /// Connection for reads which may lag behind, see `reader`. Inside a transaction the
+    /// transaction is used, so it sees its own writes.
+    async fn acquire_reader(&self) -> Result<Connection<'_>, Error> {
+        Box::pin(self.acquire_from(self.reader())).await
+    }
+
+    async fn acquire_from(&self, pool: &PgPool) -> Result<Connection<'_>, Error> {
+        let connection = match &self.transaction {
+            Some(transaction) => {
+                MutexGuard::try_map(transaction.lock().await, |transaction| transaction.as_mut())
+                    .map(Connection::Transaction)
+                    .map_err(|_| sqlx::Error::Protocol("Transaction already committed".into()))
+            }
+            // Boxed like the futures of sqlx' executor methods, this one is awaited by every
+            // operation and would blow up the size of every handler future otherwise
+            None => Box::pin(pool.acquire())
+                .await
+                .map(|connection| Connection::Pooled(Box::new(connection))),
+        };
+
+        match connection {
+            Ok(connection) => Ok(connection),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Starts a transaction on the primary. All operations of the returned store run in it
+    /// until `commit`. If the store is dropped before, e.g. when a handler returns early with
+    /// an error, the transaction is rolled back.
+    ///
+    /// Transactions do not nest, `begin` on a transaction starts a separate one.
+    pub async fn begin(&self) -> Result<Store, Error> {
+        match self.connection.begin().await {
+            Ok(transaction) => Ok(Store {
+                transaction: Some(Arc::new(Mutex::new(Some(transaction)))),
+                ..self.clone()
+            }),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Commits the transaction started with `begin`. Outside of a transaction every operation
+    /// is committed on its own, so there is nothing left to do.
+    pub async fn commit(&self) -> Result<(), Error> {
+        let transaction = match &self.transaction {
+            Some(transaction) => transaction.lock().await.take(),
+            None => return Ok(()),
         };
 
-        Store {
-            connection: db_pool,
+        let res = match transaction {
+            Some(transaction) => transaction.commit().await,
+            None => Err(sqlx::Error::Protocol(
+                "Transaction already committed".into(),
+            )),
+        };
+        match res {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Checks the replicas every `interval` until `stopped` turns `true`. Replicas which cannot
+    /// be reached, or replay the primary more than `max_lag_secs` behind, are not read from.
+    pub fn monitor_replicas(
+        &self,
+        interval: Duration,
+        max_lag_secs: u64,
+        mut stopped: watch::Receiver<bool>,
+    ) -> JoinHandle<()> {
+        let store = self.clone();
+        tokio::spawn(async move {
+            if store.replicas.is_empty() {
+                return;
+            }
+            let mut first_check = true;
+            while !*stopped.borrow() {
+                for (index, replica) in store.replicas.iter().enumerate() {
+                    let problem = match Self::replication_lag(&replica.pool).await {
+                        Ok(lag_secs) if lag_secs <= max_lag_secs as f64 => None,
+                        Ok(lag_secs) => Some(format!("lags {:.1}s behind", lag_secs)),
+                        Err(e) => Some(format!("cannot be reached: {}", e)),
+                    };
+                    // Only the first result and changes are logged, checks run every few seconds
+                    let healthy = problem.is_none();
+                    if replica.healthy.swap(healthy, Ordering::Relaxed) != healthy || first_check {
+                        match problem {
+                            Some(problem) => tracing::event!(
+                                tracing::Level::WARN,
+                                "Replica {} {}, reading from the primary instead",
+                                index,
+                                problem
+                            ),
+                            None => tracing::event!(
+                                tracing::Level::INFO,
+                                "Replica {} is healthy",
+                                index
+                            ),
+                        }
+                    }
+                }
+
+                first_check = false;
+
+                tokio::select! {
+                    _ = tokio::time::sleep(interval) => {}
+                    _ = stopped.changed() => {}
+                }
+            }
+        })
+    }
+
+    /// Seconds the replica replays behind the primary, 0 while it replayed everything received
+    async fn replication_lag(pool: &PgPool) -> Result<f64, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"SELECT CASE
+                WHEN pg_last_wal_receive_lsn() = pg_last_wal_replay_lsn() THEN 0
+                ELSE COALESCE(
+                    EXTRACT(EPOCH FROM NOW() - pg_last_xact_replay_timestamp())::DOUBLE PRECISION,
+                    0
+                )
+            END AS "lag!""#
+        )
+        .fetch_one(pool)
+        .await
+    }
+
+    /// Closes the primary and replica pools
+    pub async fn close(&self) {
+        self.connection.close().await;
+        for replica in self.replicas.iter() {
+            replica.pool.close().await;
         }
     }
 
+    /// Lists questions. If `viewer` is set, each question tells whether the viewer bookmarked it
+    /// and the list is read from the primary, so the viewer's latest bookmarks are included.
     pub async fn get_questions(
         &self,
         limit: Option<u32>,
         offset: u32,
+        viewer: Option<AccountId>,
     ) -> Result<Vec<Question>, Error> {
-        match sqlx::query(
-            "SELECT * from questions
+        let _timer = metrics::store_query("get_questions");
+        let mut conn = match viewer {
+            Some(_) => self.acquire().await?,
+            None => self.acquire_reader().await?,
+        };
+        match sqlx::query_as!(
+            QuestionRow,
+            r#"SELECT questions.id, questions.title, questions.content, questions.tags,
+                questions.account_id, questions.close_reason, questions.close_message,
+                questions.duplicate_of, questions.closed_on,
+                GREATEST(questions.updated_on, accounts.updated_on) AS "updated_on!",
+                accounts.display_name, accounts.avatar_url,
+                CASE WHEN $3::INTEGER IS NOT NULL THEN EXISTS (
+                    SELECT 1 FROM bookmarks
+                    WHERE bookmarks.question_id = questions.id AND bookmarks.account_id = $3
+                ) END AS bookmarked
+            FROM questions
+            LEFT JOIN accounts ON accounts.id = questions.account_id
+            WHERE questions.visibility = 'visible'
+            ORDER BY questions.id
             LIMIT $1
-            OFFSET $2",
+            OFFSET $2"#,
+            // Postgres expects i64, but we want to use u32
+            limit.map(i64::from),
+            i64::from(offset),
+            viewer.map(|account_id| account_id.0),
         )
-        // sqlx expects i32, but we want to use u32
-        .bind(limit.map(|u| u as i32))
-        .bind(offset as i32)
-        .map(|row: PgRow| Question {
-            id: QuestionId(row.get("id")),
-            title: row.get("title"),
-            content: row.get("content"),
-            tags: row.get("tags"),
-        })
-        .fetch_all(&self.connection)
+        .try_map(Question::try_from)
+        .fetch_all(&mut *conn)
         .await
         {
             Ok(questions) => Ok(questions),
@@ -57,30 +744,144 @@ impl Store {
         }
     }
 
+    /// Returns a visible question. If `viewer` is set, it tells whether the viewer bookmarked it
+    /// and is read from the primary like `get_questions`, otherwise from a healthy replica.
+    pub async fn get_question(
+        &self,
+        question_id: i32,
+        viewer: Option<AccountId>,
+    ) -> Result<Question, Error> {
+        let primary = viewer.is_some();
+        self.select_question(question_id, viewer, primary).await
+    }
+
+    /// Returns a visible question from the primary, e.g. right after it was written to
+    /// publish it on the live feed
+    pub async fn get_latest_question(&self, question_id: i32) -> Result<Question, Error> {
+        self.select_question(question_id, None, true).await
+    }
+
+    async fn select_question(
+        &self,
+        question_id: i32,
+        viewer: Option<AccountId>,
+        primary: bool,
+    ) -> Result<Question, Error> {
+        let _timer = metrics::store_query("get_question");
+        let mut conn = if primary {
+            self.acquire().await?
+        } else {
+            self.acquire_reader().await?
+        };
+        match sqlx::query_as!(
+            QuestionRow,
+            r#"SELECT questions.id, questions.title, questions.content, questions.tags,
+                questions.account_id, questions.close_reason, questions.close_message,
+                questions.duplicate_of, questions.closed_on,
+                GREATEST(questions.updated_on, accounts.updated_on) AS "updated_on!",
+                accounts.display_name, accounts.avatar_url,
+                CASE WHEN $2::INTEGER IS NOT NULL THEN EXISTS (
+                    SELECT 1 FROM bookmarks
+                    WHERE bookmarks.question_id = questions.id AND bookmarks.account_id = $2
+                ) END AS bookmarked
+            FROM questions
+            LEFT JOIN accounts ON accounts.id = questions.account_id
+            WHERE questions.id = $1 AND questions.visibility = 'visible'"#,
+            question_id,
+            viewer.map(|account_id| account_id.0),
+        )
+        .try_map(Question::try_from)
+        .fetch_optional(&mut *conn)
+        .await
+        {
+            Ok(Some(question)) => Ok(question),
+            Ok(None) => Err(Error::QuestionNotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Returns a visible question like `get_question` for `viewer` and locks it until the
+    /// transaction ends, so it cannot change between checking and updating it
+    pub async fn lock_question(
+        &self,
+        question_id: i32,
+        viewer: &AccountId,
+    ) -> Result<Question, Error> {
+        let _timer = metrics::store_query("lock_question");
+        let mut conn = self.acquire().await?;
+        match sqlx::query_as!(
+            QuestionRow,
+            r#"SELECT questions.id, questions.title, questions.content, questions.tags,
+                questions.account_id, questions.close_reason, questions.close_message,
+                questions.duplicate_of, questions.closed_on,
+                GREATEST(questions.updated_on, accounts.updated_on) AS "updated_on!",
+                accounts.display_name, accounts.avatar_url,
+                EXISTS (
+                    SELECT 1 FROM bookmarks
+                    WHERE bookmarks.question_id = questions.id AND bookmarks.account_id = $2
+                ) AS bookmarked
+            FROM questions
+            LEFT JOIN accounts ON accounts.id = questions.account_id
+            WHERE questions.id = $1 AND questions.visibility = 'visible'
+            FOR UPDATE OF questions"#,
+            question_id,
+            viewer.0,
+        )
+        .try_map(Question::try_from)
+        .fetch_optional(&mut *conn)
+        .await
+        {
+            Ok(Some(question)) => Ok(question),
+            Ok(None) => Err(Error::QuestionNotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
     pub async fn add_question(
         &self,
         new_question: NewQuestion,
         account_id: AccountId,
+        visibility: Visibility,
     ) -> Result<Question, Error> {
-        match sqlx::query(
-            "INSERT INTO questions (title, content, tags, account_id)
-            VALUES ($1, $2, $3, $4)
-            RETURNING id, title, content, tags",
-        )
-        .bind(new_question.title)
-        .bind(new_question.content)
-        .bind(new_question.tags)
-        .bind(account_id.0)
-        .map(|row: PgRow| Question {
-            id: QuestionId(row.get("id")),
-            title: row.get("title"),
-            content: row.get("content"),
-            tags: row.get("tags"),
-        })
-        .fetch_one(&self.connection)
+        let _timer = metrics::store_query("add_question");
+        let mut conn = self.acquire().await?;
+        match sqlx::query_as!(
+            QuestionRow,
+            r#"WITH question AS (
+                INSERT INTO questions (title, content, tags, account_id, visibility)
+                VALUES ($1, $2, $3, $4, $5)
+                RETURNING id, title, content, tags, account_id,
+                    close_reason, close_message, duplicate_of, closed_on, updated_on
+            )
+            SELECT question.id AS "id!", question.title AS "title!",
+                question.content AS "content!", question.tags AS "tags!",
+                question.account_id AS "account_id!", question.close_reason,
+                question.close_message, question.duplicate_of, question.closed_on,
+                GREATEST(question.updated_on, accounts.updated_on) AS "updated_on!",
+                accounts.display_name, accounts.avatar_url, NULL::BOOLEAN AS bookmarked
+            FROM question
+            LEFT JOIN accounts ON accounts.id = question.account_id"#,
+            new_question.title,
+            new_question.content,
+            // Tags are optional in the API, but not in the table
+            new_question.tags.as_deref().unwrap_or_default(),
+            account_id.0,
+            visibility.as_str(),
+        )
+        .try_map(Question::try_from)
+        .fetch_one(&mut *conn)
         .await
         {
-            Ok(question) => Ok(question),
+            Ok(question) => {
+                Self::notify_feed(&mut conn, FeedEventKind::QuestionCreated, question.id.0).await;
+                Ok(question)
+            }
             Err(e) => {
                 tracing::event!(tracing::Level::ERROR, "{:?}", e);
                 Err(Error::DatabaseQueryError(e))
@@ -88,30 +889,58 @@ impl Store {
         }
     }
 
+    /// Updates a question. A visible question is hidden again with `visibility`, e.g. while an
+    /// edit is reviewed, pending and removed questions keep their visibility. The returned
+    /// question tells whether `viewer` bookmarked it, like `get_question`.
     pub async fn update_question(
         &self,
         question: Question,
         question_id: i32,
+        visibility: Option<Visibility>,
+        viewer: &AccountId,
     ) -> Result<Question, Error> {
-        match sqlx::query(
-            "UPDATE questions SET title = $1, content = $2, tags = $3
-            WHERE id = $4
-            RETURNING id, title, content, tags",
-        )
-        .bind(question.title)
-        .bind(question.content)
-        .bind(question.tags)
-        .bind(question_id)
-        .map(|row: PgRow| Question {
-            id: QuestionId(row.get("id")),
-            title: row.get("title"),
-            content: row.get("content"),
-            tags: row.get("tags"),
-        })
-        .fetch_one(&self.connection)
+        let _timer = metrics::store_query("update_question");
+        let mut conn = self.acquire().await?;
+        match sqlx::query_as!(
+            QuestionRow,
+            r#"WITH question AS (
+                UPDATE questions
+                SET title = $1, content = $2, tags = $3,
+                    visibility = CASE
+                        WHEN visibility = 'visible' THEN COALESCE($5, visibility)
+                        ELSE visibility
+                    END
+                WHERE id = $4
+                RETURNING id, title, content, tags, account_id,
+                    close_reason, close_message, duplicate_of, closed_on, updated_on
+            )
+            SELECT question.id AS "id!", question.title AS "title!",
+                question.content AS "content!", question.tags AS "tags!",
+                question.account_id AS "account_id!", question.close_reason,
+                question.close_message, question.duplicate_of, question.closed_on,
+                GREATEST(question.updated_on, accounts.updated_on) AS "updated_on!",
+                accounts.display_name, accounts.avatar_url,
+                EXISTS (
+                    SELECT 1 FROM bookmarks
+                    WHERE bookmarks.question_id = question.id AND bookmarks.account_id = $6
+                ) AS bookmarked
+            FROM question
+            LEFT JOIN accounts ON accounts.id = question.account_id"#,
+            question.title,
+            question.content,
+            question.tags.as_deref().unwrap_or_default(),
+            question_id,
+            visibility.map(|visibility| visibility.as_str()),
+            viewer.0,
+        )
+        .try_map(Question::try_from)
+        .fetch_one(&mut *conn)
         .await
         {
-            Ok(question) => Ok(question),
+            Ok(question) => {
+                Self::notify_feed(&mut conn, FeedEventKind::QuestionUpdated, question.id.0).await;
+                Ok(question)
+            }
             Err(e) => {
                 tracing::event!(tracing::Level::ERROR, "{:?}", e);
                 Err(Error::DatabaseQueryError(e))
@@ -124,11 +953,15 @@ impl Store {
         question_id: i32,
         account_id: AccountId,
     ) -> Result<bool, Error> {
-        match sqlx::query("DELETE FROM questions WHERE id = $1 AND account_id = $2")
-            .bind(question_id)
-            .bind(account_id.0)
-            .execute(&self.connection)
-            .await
+        let _timer = metrics::store_query("delete_question");
+        let mut conn = self.acquire().await?;
+        match sqlx::query!(
+            "DELETE FROM questions WHERE id = $1 AND account_id = $2",
+            question_id,
+            account_id.0,
+        )
+        .execute(&mut *conn)
+        .await
         {
             Ok(_) => Ok(true),
             Err(e) => {
@@ -138,28 +971,71 @@ impl Store {
         }
     }
 
+    /// Removes the reputation earned with a question and its answers, e.g. before deleting it.
+    ///
+    /// Returns the accounts whose reputation has to be recomputed.
+    pub async fn remove_question_reputation_events(
+        &self,
+        question_id: i32,
+    ) -> Result<Vec<AccountId>, Error> {
+        let _timer = metrics::store_query("remove_question_reputation_events");
+        let mut conn = self.acquire().await?;
+        match sqlx::query!(
+            "DELETE FROM reputation_events
+            WHERE question_id = $1
+            OR answer_id IN (SELECT id FROM answers WHERE corresponding_question = $1)
+            RETURNING account_id",
+            question_id,
+        )
+        .map(|row| AccountId(row.account_id))
+        .fetch_all(&mut *conn)
+        .await
+        {
+            Ok(mut account_ids) => {
+                account_ids.sort_by_key(|account_id| account_id.0);
+                account_ids.dedup();
+                Ok(account_ids)
+            }
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
     pub async fn add_answer(
         &self,
         new_answer: NewAnswer,
         account_id: AccountId,
+        visibility: Visibility,
     ) -> Result<Answer, Error> {
-        match sqlx::query(
-            "INSERT INTO answers (content, corresponding_question, account_id)
-            VALUES ($1, $2, $3)
-            RETURNING id, content, corresponding_question",
-        )
-        .bind(new_answer.content)
-        .bind(new_answer.question_id.0)
-        .bind(account_id.0)
-        .map(|row: PgRow| Answer {
-            id: AnswerId(row.get("id")),
-            content: row.get("content"),
-            question_id: QuestionId(row.get("corresponding_question")),
-        })
-        .fetch_one(&self.connection)
+        let _timer = metrics::store_query("add_answer");
+        let mut conn = self.acquire().await?;
+        match sqlx::query_as!(
+            AnswerRow,
+            r#"WITH answer AS (
+                INSERT INTO answers (content, corresponding_question, account_id, visibility)
+                VALUES ($1, $2, $3, $4)
+                RETURNING id, content, corresponding_question, account_id
+            )
+            SELECT answer.id AS "id!", answer.content AS "content!",
+                answer.corresponding_question AS "corresponding_question!",
+                answer.account_id AS "account_id!", accounts.display_name, accounts.avatar_url
+            FROM answer
+            LEFT JOIN accounts ON accounts.id = answer.account_id"#,
+            new_answer.content,
+            new_answer.question_id.0,
+            account_id.0,
+            visibility.as_str(),
+        )
+        .map(Answer::from)
+        .fetch_one(&mut *conn)
         .await
         {
-            Ok(answer) => Ok(answer),
+            Ok(answer) => {
+                Self::notify_feed(&mut conn, FeedEventKind::AnswerCreated, answer.id.0).await;
+                Ok(answer)
+            }
             Err(e) => {
                 tracing::event!(tracing::Level::ERROR, "{:?}", e);
                 Err(Error::DatabaseQueryError(e))
@@ -168,55 +1044,39 @@ impl Store {
     }
 
     pub async fn add_account(&self, account: Account) -> Result<Account, Error> {
-        match sqlx::query(
+        let _timer = metrics::store_query("add_account");
+        let mut conn = self.acquire().await?;
+        match sqlx::query_as!(
+            AccountRow,
             "INSERT INTO accounts (email, password)
             VALUES ($1, $2)
-            RETURNING *",
-        )
-        .bind(account.email)
-        .bind(account.password)
-        .map(|row: PgRow| Account {
-            id: Some(AccountId(row.get("id"))),
-            email: row.get("email"),
-            password: row.get("password"),
-        })
-        .fetch_one(&self.connection)
+            RETURNING id, email, password",
+            account.email,
+            account.password,
+        )
+        .map(Account::from)
+        .fetch_one(&mut *conn)
         .await
         {
             Ok(account) => Ok(account),
             Err(e) => {
-                tracing::event!(
-                    tracing::Level::ERROR,
-                    // Records an event with custom fields:
-                    // https://docs.rs/tracing/latest/tracing/index.html#recording-fields
-
-                    // Retrieve field from DatabaseError
-                    // see https://docs.rs/sqlx/latest/sqlx/error/trait.DatabaseError.html
-                    code = e
-                        .as_database_error()
-                        .unwrap()
-                        .code()
-                        .unwrap()
-                        .parse::<i32>()
-                        .unwrap(),
-                    db_message = e.as_database_error().unwrap().message(),
-                    constraint = e.as_database_error().unwrap().constraint().unwrap()
-                );
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
                 Err(Error::DatabaseQueryError(e))
             }
         }
     }
 
     pub async fn get_account(&self, email: String) -> Result<Account, Error> {
-        match sqlx::query("SELECT * from accounts WHERE email = $1")
-            .bind(email)
-            .map(|row: PgRow| Account {
-                id: Some(AccountId(row.get("id"))),
-                email: row.get("email"),
-                password: row.get("password"),
-            })
-            .fetch_one(&self.connection)
-            .await
+        let _timer = metrics::store_query("get_account");
+        let mut conn = self.acquire().await?;
+        match sqlx::query_as!(
+            AccountRow,
+            "SELECT id, email, password from accounts WHERE lower(email) = lower($1)",
+            email,
+        )
+        .map(Account::from)
+        .fetch_one(&mut *conn)
+        .await
         {
             Ok(account) => Ok(account),
             Err(e) => {
@@ -231,13 +1091,1836 @@ impl Store {
         question_id: i32,
         account_id: &AccountId,
     ) -> Result<bool, Error> {
-        match sqlx::query("SELECT * from questions WHERE id = $1 and account_id = $2")
-            .bind(question_id)
-            .bind(account_id.0)
-            .fetch_optional(&self.connection)
-            .await
+        let _timer = metrics::store_query("is_question_owner");
+        let mut conn = self.acquire().await?;
+        match sqlx::query_scalar!(
+            r#"SELECT EXISTS (
+                SELECT 1 from questions WHERE id = $1 and account_id = $2
+            ) AS "owner!""#,
+            question_id,
+            account_id.0,
+        )
+        .fetch_one(&mut *conn)
+        .await
+        {
+            Ok(owner) => Ok(owner),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Returns the profile of an account. Read from the primary if `viewer` is the account
+    /// itself, so it sees its own changes, e.g. right after registering or updating it.
+    pub async fn get_profile(
+        &self,
+        account_id: AccountId,
+        viewer: Option<&AccountId>,
+    ) -> Result<Profile, Error> {
+        let _timer = metrics::store_query("get_profile");
+        let mut conn = match viewer {
+            Some(viewer) if *viewer == account_id => self.acquire().await?,
+            _ => self.acquire_reader().await?,
+        };
+        match sqlx::query_as!(
+            ProfileRow,
+            "SELECT id, display_name, bio, avatar_url, reputation, created_on
+            FROM accounts
+            WHERE id = $1",
+            account_id.0,
+        )
+        .map(Profile::from)
+        .fetch_optional(&mut *conn)
+        .await
+        {
+            Ok(Some(profile)) => Ok(profile),
+            Ok(None) => Err(Error::AccountNotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn update_profile(
+        &self,
+        account_id: AccountId,
+        profile: UpdateProfile,
+    ) -> Result<Profile, Error> {
+        let _timer = metrics::store_query("update_profile");
+        let mut conn = self.acquire().await?;
+        match sqlx::query_as!(
+            ProfileRow,
+            "UPDATE accounts SET display_name = $1, bio = $2, avatar_url = $3
+            WHERE id = $4
+            RETURNING id, display_name, bio, avatar_url, reputation, created_on",
+            profile.display_name,
+            profile.bio,
+            profile.avatar_url,
+            account_id.0,
+        )
+        .map(Profile::from)
+        .fetch_optional(&mut *conn)
+        .await
+        {
+            Ok(Some(profile)) => Ok(profile),
+            Ok(None) => Err(Error::AccountNotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn get_questions_by_account(
+        &self,
+        account_id: AccountId,
+    ) -> Result<Vec<Question>, Error> {
+        let _timer = metrics::store_query("get_questions_by_account");
+        let mut conn = self.acquire_reader().await?;
+        match sqlx::query_as!(
+            QuestionRow,
+            r#"SELECT questions.id, questions.title, questions.content, questions.tags,
+                questions.account_id, questions.close_reason, questions.close_message,
+                questions.duplicate_of, questions.closed_on,
+                GREATEST(questions.updated_on, accounts.updated_on) AS "updated_on!",
+                accounts.display_name, accounts.avatar_url, NULL::BOOLEAN AS bookmarked
+            FROM questions
+            LEFT JOIN accounts ON accounts.id = questions.account_id
+            WHERE questions.account_id = $1 AND questions.visibility = 'visible'
+            ORDER BY questions.created_on DESC, questions.id DESC"#,
+            account_id.0,
+        )
+        .try_map(Question::try_from)
+        .fetch_all(&mut *conn)
+        .await
+        {
+            Ok(questions) => Ok(questions),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn get_answers_by_account(
+        &self,
+        account_id: AccountId,
+    ) -> Result<Vec<Answer>, Error> {
+        let _timer = metrics::store_query("get_answers_by_account");
+        let mut conn = self.acquire_reader().await?;
+        match sqlx::query_as!(
+            AnswerRow,
+            "SELECT answers.id, answers.content, answers.corresponding_question,
+                answers.account_id, accounts.display_name, accounts.avatar_url
+            FROM answers
+            LEFT JOIN accounts ON accounts.id = answers.account_id
+            WHERE answers.account_id = $1 AND answers.visibility = 'visible'
+            ORDER BY answers.created_on DESC, answers.id DESC",
+            account_id.0,
+        )
+        .map(Answer::from)
+        .fetch_all(&mut *conn)
+        .await
+        {
+            Ok(answers) => Ok(answers),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn get_question_author(&self, question_id: i32) -> Result<AccountId, Error> {
+        let _timer = metrics::store_query("get_question_author");
+        let mut conn = self.acquire().await?;
+        match sqlx::query!(
+            "SELECT account_id from questions WHERE id = $1",
+            question_id
+        )
+        .map(|row| AccountId(row.account_id))
+        .fetch_optional(&mut *conn)
+        .await
+        {
+            Ok(Some(account_id)) => Ok(account_id),
+            Ok(None) => Err(Error::QuestionNotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn get_answer(&self, answer_id: i32) -> Result<Answer, Error> {
+        let _timer = metrics::store_query("get_answer");
+        let mut conn = self.acquire().await?;
+        match sqlx::query_as!(
+            AnswerRow,
+            "SELECT answers.id, answers.content, answers.corresponding_question,
+                answers.account_id, accounts.display_name, accounts.avatar_url
+            FROM answers
+            LEFT JOIN accounts ON accounts.id = answers.account_id
+            WHERE answers.id = $1",
+            answer_id,
+        )
+        .map(Answer::from)
+        .fetch_optional(&mut *conn)
+        .await
+        {
+            Ok(Some(answer)) => Ok(answer),
+            Ok(None) => Err(Error::AnswerNotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn accept_answer(&self, question_id: i32, answer_id: i32) -> Result<bool, Error> {
+        let _timer = metrics::store_query("accept_answer");
+        let mut conn = self.acquire().await?;
+        match sqlx::query!(
+            "UPDATE questions SET accepted_answer_id = $1 WHERE id = $2",
+            answer_id,
+            question_id,
+        )
+        .execute(&mut *conn)
+        .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Stores the vote of an account on a question, replacing any earlier vote
+    pub async fn vote_question(
+        &self,
+        question_id: i32,
+        account_id: &AccountId,
+        direction: VoteDirection,
+    ) -> Result<bool, Error> {
+        let _timer = metrics::store_query("vote_question");
+        let mut conn = self.acquire().await?;
+        match sqlx::query!(
+            "INSERT INTO question_votes (account_id, question_id, value)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (account_id, question_id) DO UPDATE SET value = EXCLUDED.value",
+            account_id.0,
+            question_id,
+            direction.value(),
+        )
+        .execute(&mut *conn)
+        .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Stores the vote of an account on an answer, replacing any earlier vote
+    pub async fn vote_answer(
+        &self,
+        answer_id: i32,
+        account_id: &AccountId,
+        direction: VoteDirection,
+    ) -> Result<bool, Error> {
+        let _timer = metrics::store_query("vote_answer");
+        let mut conn = self.acquire().await?;
+        match sqlx::query!(
+            "INSERT INTO answer_votes (account_id, answer_id, value)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (account_id, answer_id) DO UPDATE SET value = EXCLUDED.value",
+            account_id.0,
+            answer_id,
+            direction.value(),
+        )
+        .execute(&mut *conn)
+        .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Appends `event` to the reputation ledger. Earlier events of one of the `replaces` kinds
+    /// by the same actor on the same question/answer are removed, so changing a vote or
+    /// accepting another answer does not count twice.
+    ///
+    /// Returns the accounts whose reputation has to be recomputed.
+    pub async fn replace_reputation_event(
+        &self,
+        event: ReputationEvent,
+        replaces: &[ReputationEventKind],
+    ) -> Result<Vec<AccountId>, Error> {
+        let _timer = metrics::store_query("replace_reputation_event");
+        let mut conn = self.acquire().await?;
+        match sqlx::query!(
+            r#"WITH removed AS (
+                DELETE FROM reputation_events
+                WHERE kind = ANY($1)
+                AND actor_account_id = $2
+                AND question_id IS NOT DISTINCT FROM $3
+                AND answer_id IS NOT DISTINCT FROM $4
+                RETURNING account_id
+            ), inserted AS (
+                INSERT INTO reputation_events
+                    (account_id, kind, points, actor_account_id, question_id, answer_id)
+                VALUES ($5, $6, $7, $2, $3, $4)
+                RETURNING account_id
+            )
+            SELECT account_id AS "account_id!" FROM removed
+            UNION
+            SELECT account_id FROM inserted"#,
+            &replaces
+                .iter()
+                .map(|kind| kind.as_str().to_string())
+                .collect::<Vec<String>>(),
+            event.actor_account_id.0,
+            event.question_id,
+            event.answer_id,
+            event.account_id.0,
+            event.kind.as_str(),
+            event.points,
+        )
+        .map(|row| AccountId(row.account_id))
+        .fetch_all(&mut *conn)
+        .await
+        {
+            Ok(account_ids) => Ok(account_ids),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Recomputes the cached `accounts.reputation` from the reputation ledger
+    pub async fn recompute_reputation(&self, account_ids: Vec<AccountId>) -> Result<bool, Error> {
+        let _timer = metrics::store_query("recompute_reputation");
+        let mut conn = self.acquire().await?;
+        match sqlx::query!(
+            "UPDATE accounts SET reputation = COALESCE(
+                (SELECT SUM(points) FROM reputation_events
+                WHERE reputation_events.account_id = accounts.id), 0)
+            WHERE id = ANY($1)",
+            &account_ids.iter().map(|id| id.0).collect::<Vec<i32>>(),
+        )
+        .execute(&mut *conn)
+        .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Fails with `Error::InsufficientReputation` unless the account may perform `privilege`
+    pub async fn require_privilege(
+        &self,
+        account_id: &AccountId,
+        privilege: Privilege,
+    ) -> Result<(), Error> {
+        let _timer = metrics::store_query("require_privilege");
+        let mut conn = self.acquire().await?;
+        match sqlx::query!(
+            "SELECT reputation, is_moderator from accounts WHERE id = $1",
+            account_id.0,
+        )
+        .map(|row| (row.reputation, row.is_moderator))
+        .fetch_optional(&mut *conn)
+        .await
+        {
+            Ok(Some((_, true))) => Ok(()),
+            Ok(Some((reputation, false))) if reputation >= privilege.required_reputation() => {
+                Ok(())
+            }
+            Ok(Some(_)) => Err(Error::InsufficientReputation(
+                privilege.required_reputation(),
+            )),
+            Ok(None) => Err(Error::AccountNotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn add_comment(
+        &self,
+        question_id: i32,
+        new_comment: NewComment,
+        account_id: AccountId,
+    ) -> Result<Comment, Error> {
+        let _timer = metrics::store_query("add_comment");
+        let mut conn = self.acquire().await?;
+        match sqlx::query_as!(
+            CommentRow,
+            r#"WITH comment AS (
+                INSERT INTO comments (content, question_id, account_id)
+                VALUES ($1, $2, $3)
+                RETURNING id, content, question_id, account_id
+            )
+            SELECT comment.id AS "id!", comment.content AS "content!",
+                comment.question_id AS "question_id!", comment.account_id AS "account_id!",
+                accounts.display_name, accounts.avatar_url
+            FROM comment
+            LEFT JOIN accounts ON accounts.id = comment.account_id"#,
+            new_comment.content,
+            question_id,
+            account_id.0,
+        )
+        .map(Comment::from)
+        .fetch_one(&mut *conn)
+        .await
+        {
+            Ok(comment) => Ok(comment),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn get_comments(&self, question_id: i32) -> Result<Vec<Comment>, Error> {
+        let _timer = metrics::store_query("get_comments");
+        let mut conn = self.acquire_reader().await?;
+        match sqlx::query_as!(
+            CommentRow,
+            "SELECT comments.id, comments.content, comments.question_id,
+                comments.account_id, accounts.display_name, accounts.avatar_url
+            FROM comments
+            JOIN questions ON questions.id = comments.question_id
+            LEFT JOIN accounts ON accounts.id = comments.account_id
+            WHERE comments.question_id = $1
+                AND comments.visibility = 'visible'
+                AND questions.visibility = 'visible'
+            ORDER BY comments.created_on, comments.id",
+            question_id,
+        )
+        .map(Comment::from)
+        .fetch_all(&mut *conn)
+        .await
+        {
+            Ok(comments) => Ok(comments),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn add_bookmark(
+        &self,
+        question_id: i32,
+        account_id: &AccountId,
+    ) -> Result<bool, Error> {
+        let _timer = metrics::store_query("add_bookmark");
+        let mut conn = self.acquire().await?;
+        match sqlx::query!(
+            "INSERT INTO bookmarks (account_id, question_id)
+            VALUES ($1, $2)
+            ON CONFLICT DO NOTHING",
+            account_id.0,
+            question_id,
+        )
+        .execute(&mut *conn)
+        .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn delete_bookmark(
+        &self,
+        question_id: i32,
+        account_id: &AccountId,
+    ) -> Result<bool, Error> {
+        let _timer = metrics::store_query("delete_bookmark");
+        let mut conn = self.acquire().await?;
+        match sqlx::query!(
+            "DELETE FROM bookmarks WHERE account_id = $1 AND question_id = $2",
+            account_id.0,
+            question_id,
+        )
+        .execute(&mut *conn)
+        .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Lists the questions bookmarked by an account, most recently bookmarked first
+    pub async fn get_bookmarked_questions(
+        &self,
+        account_id: &AccountId,
+        limit: Option<u32>,
+        offset: u32,
+    ) -> Result<Vec<Question>, Error> {
+        let _timer = metrics::store_query("get_bookmarked_questions");
+        let mut conn = self.acquire().await?;
+        match sqlx::query_as!(
+            QuestionRow,
+            r#"SELECT questions.id, questions.title, questions.content, questions.tags,
+                questions.account_id, questions.close_reason, questions.close_message,
+                questions.duplicate_of, questions.closed_on,
+                GREATEST(questions.updated_on, accounts.updated_on) AS "updated_on!",
+                accounts.display_name, accounts.avatar_url, TRUE AS bookmarked
+            FROM bookmarks
+            JOIN questions ON questions.id = bookmarks.question_id
+            LEFT JOIN accounts ON accounts.id = questions.account_id
+            WHERE bookmarks.account_id = $1 AND questions.visibility = 'visible'
+            ORDER BY bookmarks.created_on DESC
+            LIMIT $2
+            OFFSET $3"#,
+            account_id.0,
+            limit.map(i64::from),
+            i64::from(offset),
+        )
+        .try_map(Question::try_from)
+        .fetch_all(&mut *conn)
+        .await
+        {
+            Ok(questions) => Ok(questions),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn follow_question(
+        &self,
+        question_id: i32,
+        account_id: &AccountId,
+    ) -> Result<bool, Error> {
+        let _timer = metrics::store_query("follow_question");
+        let mut conn = self.acquire().await?;
+        match sqlx::query!(
+            "INSERT INTO question_follows (account_id, question_id)
+            VALUES ($1, $2)
+            ON CONFLICT DO NOTHING",
+            account_id.0,
+            question_id,
+        )
+        .execute(&mut *conn)
+        .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn unfollow_question(
+        &self,
+        question_id: i32,
+        account_id: &AccountId,
+    ) -> Result<bool, Error> {
+        let _timer = metrics::store_query("unfollow_question");
+        let mut conn = self.acquire().await?;
+        match sqlx::query!(
+            "DELETE FROM question_follows WHERE account_id = $1 AND question_id = $2",
+            account_id.0,
+            question_id,
+        )
+        .execute(&mut *conn)
+        .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn follow_tag(&self, tag: String, account_id: &AccountId) -> Result<bool, Error> {
+        let _timer = metrics::store_query("follow_tag");
+        let mut conn = self.acquire().await?;
+        match sqlx::query!(
+            "INSERT INTO tag_follows (account_id, tag)
+            VALUES ($1, $2)
+            ON CONFLICT DO NOTHING",
+            account_id.0,
+            tag,
+        )
+        .execute(&mut *conn)
+        .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn unfollow_tag(&self, tag: String, account_id: &AccountId) -> Result<bool, Error> {
+        let _timer = metrics::store_query("unfollow_tag");
+        let mut conn = self.acquire().await?;
+        match sqlx::query!(
+            "DELETE FROM tag_follows WHERE account_id = $1 AND tag = $2",
+            account_id.0,
+            tag,
+        )
+        .execute(&mut *conn)
+        .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Creates a notification for every account following the question or one of its tags,
+    /// except for the account which caused it
+    pub async fn notify_followers(
+        &self,
+        kind: NotificationKind,
+        question_id: i32,
+        answer_id: Option<i32>,
+        comment_id: Option<i32>,
+        actor_account_id: &AccountId,
+    ) -> Result<bool, Error> {
+        let _timer = metrics::store_query("notify_followers");
+        let mut conn = self.acquire().await?;
+        match sqlx::query!(
+            "INSERT INTO notifications
+                (account_id, kind, question_id, answer_id, comment_id, actor_account_id)
+            SELECT followers.account_id, $1, $2, $3, $4, $5
+            FROM (
+                SELECT account_id FROM question_follows WHERE question_id = $2
+                UNION
+                SELECT tag_follows.account_id FROM tag_follows
+                JOIN questions ON tag_follows.tag = ANY(questions.tags)
+                WHERE questions.id = $2
+            ) AS followers
+            WHERE followers.account_id <> $5",
+            kind.as_str(),
+            question_id,
+            answer_id,
+            comment_id,
+            actor_account_id.0,
+        )
+        .execute(&mut *conn)
+        .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn get_notifications(
+        &self,
+        account_id: &AccountId,
+        unread_only: bool,
+        limit: Option<u32>,
+        offset: u32,
+    ) -> Result<Vec<Notification>, Error> {
+        let _timer = metrics::store_query("get_notifications");
+        let mut conn = self.acquire().await?;
+        match sqlx::query_as!(
+            NotificationRow,
+            r#"SELECT id, kind, question_id, answer_id, comment_id,
+                read_on IS NOT NULL AS "read!", created_on
+            FROM notifications
+            WHERE account_id = $1 AND ($2 = false OR read_on IS NULL)
+            ORDER BY created_on DESC
+            LIMIT $3
+            OFFSET $4"#,
+            account_id.0,
+            unread_only,
+            limit.map(i64::from),
+            i64::from(offset),
+        )
+        .try_map(Notification::try_from)
+        .fetch_all(&mut *conn)
+        .await
+        {
+            Ok(notifications) => Ok(notifications),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn mark_notification_read(
+        &self,
+        notification_id: i32,
+        account_id: &AccountId,
+    ) -> Result<bool, Error> {
+        let _timer = metrics::store_query("mark_notification_read");
+        let mut conn = self.acquire().await?;
+        match sqlx::query!(
+            "UPDATE notifications SET read_on = COALESCE(read_on, NOW())
+            WHERE id = $1 AND account_id = $2",
+            notification_id,
+            account_id.0,
+        )
+        .execute(&mut *conn)
+        .await
+        {
+            Ok(result) if result.rows_affected() == 0 => Err(Error::NotificationNotFound),
+            Ok(_) => Ok(true),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn mark_all_notifications_read(&self, account_id: &AccountId) -> Result<bool, Error> {
+        let _timer = metrics::store_query("mark_all_notifications_read");
+        let mut conn = self.acquire().await?;
+        match sqlx::query!(
+            "UPDATE notifications SET read_on = NOW()
+            WHERE account_id = $1 AND read_on IS NULL",
+            account_id.0,
+        )
+        .execute(&mut *conn)
+        .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn close_question(
+        &self,
+        question_id: i32,
+        close: CloseQuestion,
+        account_id: &AccountId,
+    ) -> Result<bool, Error> {
+        let _timer = metrics::store_query("close_question");
+        let mut conn = self.acquire().await?;
+        match sqlx::query!(
+            "UPDATE questions
+            SET close_reason = $1, close_message = $2, duplicate_of = $3, closed_by = $4,
+                closed_on = NOW()
+            WHERE id = $5",
+            close.reason.as_str(),
+            close.message,
+            close.duplicate_of.map(|id| id.0),
+            account_id.0,
+            question_id,
+        )
+        .execute(&mut *conn)
+        .await
+        {
+            Ok(result) if result.rows_affected() == 0 => Err(Error::QuestionNotFound),
+            Ok(_) => {
+                Self::notify_feed(&mut conn, FeedEventKind::QuestionUpdated, question_id).await;
+                Ok(true)
+            }
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn reopen_question(&self, question_id: i32) -> Result<bool, Error> {
+        let _timer = metrics::store_query("reopen_question");
+        let mut conn = self.acquire().await?;
+        match sqlx::query!(
+            "UPDATE questions
+            SET close_reason = NULL, close_message = NULL, duplicate_of = NULL, closed_by = NULL,
+                closed_on = NULL
+            WHERE id = $1",
+            question_id,
+        )
+        .execute(&mut *conn)
+        .await
+        {
+            Ok(result) if result.rows_affected() == 0 => Err(Error::QuestionNotFound),
+            Ok(_) => {
+                Self::notify_feed(&mut conn, FeedEventKind::QuestionUpdated, question_id).await;
+                Ok(true)
+            }
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn is_question_closed(&self, question_id: i32) -> Result<bool, Error> {
+        let _timer = metrics::store_query("is_question_closed");
+        let mut conn = self.acquire().await?;
+        match sqlx::query_scalar!(
+            r#"SELECT closed_on IS NOT NULL AS "closed!" from questions WHERE id = $1"#,
+            question_id,
+        )
+        .fetch_optional(&mut *conn)
+        .await
+        {
+            Ok(Some(closed)) => Ok(closed),
+            Ok(None) => Err(Error::QuestionNotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Fails with `Error::QuestionNotFound` unless the question is visible. Pending and removed
+    /// questions cannot be answered, voted on or commented on.
+    pub async fn require_visible_question(&self, question_id: i32) -> Result<(), Error> {
+        let _timer = metrics::store_query("require_visible_question");
+        let mut conn = self.acquire().await?;
+        match sqlx::query_scalar!(
+            r#"SELECT EXISTS (
+                SELECT 1 FROM questions WHERE id = $1 AND visibility = 'visible'
+            ) AS "visible!""#,
+            question_id,
+        )
+        .fetch_one(&mut *conn)
+        .await
+        {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(Error::QuestionNotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Fails with `Error::AnswerNotFound` unless the answer and its question are visible
+    pub async fn require_visible_answer(&self, answer_id: i32) -> Result<(), Error> {
+        let _timer = metrics::store_query("require_visible_answer");
+        let mut conn = self.acquire().await?;
+        match sqlx::query_scalar!(
+            r#"SELECT EXISTS (
+                SELECT 1 FROM answers
+                JOIN questions ON questions.id = answers.corresponding_question
+                WHERE answers.id = $1
+                    AND answers.visibility = 'visible' AND questions.visibility = 'visible'
+            ) AS "visible!""#,
+            answer_id,
+        )
+        .fetch_one(&mut *conn)
+        .await
+        {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(Error::AnswerNotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Takes a token from the rate limit bucket of `key` holding up to `burst` tokens and
+    /// refilled by `per_second` tokens. Returns the tokens left if a token was taken,
+    /// or the tokens in the bucket if it was empty.
+    pub async fn take_rate_limit_token(
+        &self,
+        key: &str,
+        burst: f64,
+        per_second: f64,
+    ) -> Result<Result<f64, f64>, Error> {
+        let _timer = metrics::store_query("take_rate_limit_token");
+        let mut conn = self.acquire().await?;
+        // The bucket is only updated if a token is left, so empty buckets keep refilling
+        let taken = sqlx::query_scalar!(
+            "INSERT INTO rate_limit_buckets (key, tokens, updated_on)
+            VALUES ($1, $2::DOUBLE PRECISION - 1, NOW())
+            ON CONFLICT (key) DO UPDATE SET
+                tokens = LEAST(
+                    $2,
+                    rate_limit_buckets.tokens
+                        + EXTRACT(EPOCH FROM NOW() - rate_limit_buckets.updated_on)::DOUBLE PRECISION
+                        * $3
+                ) - 1,
+                updated_on = NOW()
+            WHERE LEAST(
+                $2,
+                rate_limit_buckets.tokens
+                    + EXTRACT(EPOCH FROM NOW() - rate_limit_buckets.updated_on)::DOUBLE PRECISION
+                    * $3
+            ) >= 1
+            RETURNING tokens",
+            key,
+            burst,
+            per_second,
+        )
+        .fetch_optional(&mut *conn)
+        .await;
+
+        let result = match taken {
+            Ok(Some(tokens)) => Ok(Ok(tokens)),
+            Ok(None) => sqlx::query_scalar!(
+                r#"SELECT LEAST(
+                    $2,
+                    tokens + EXTRACT(EPOCH FROM NOW() - updated_on)::DOUBLE PRECISION * $3
+                ) AS "tokens!"
+                FROM rate_limit_buckets WHERE key = $1"#,
+                key,
+                burst,
+                per_second,
+            )
+            .fetch_one(&mut *conn)
+            .await
+            .map(Err),
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(taken) => Ok(taken),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Whether a question or an answer with the same content was posted within `window_secs`
+    pub async fn is_recent_duplicate(
+        &self,
+        content: &str,
+        window_secs: u64,
+    ) -> Result<bool, Error> {
+        let _timer = metrics::store_query("is_recent_duplicate");
+        let mut conn = self.acquire().await?;
+        match sqlx::query_scalar!(
+            r#"SELECT EXISTS (
+                SELECT 1 FROM questions
+                WHERE md5(content) = md5($1) AND created_on > NOW() - make_interval(secs => $2)
+                UNION ALL
+                SELECT 1 FROM answers
+                WHERE md5(content) = md5($1) AND created_on > NOW() - make_interval(secs => $2)
+            ) AS "duplicate!""#,
+            content,
+            window_secs as f64,
+        )
+        .fetch_one(&mut *conn)
+        .await
+        {
+            Ok(duplicate) => Ok(duplicate),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn enqueue_moderation_item(&self, item: NewModerationItem) -> Result<bool, Error> {
+        let _timer = metrics::store_query("enqueue_moderation_item");
+        let mut conn = self.acquire().await?;
+        match sqlx::query!(
+            "INSERT INTO moderation_items
+                (question_id, answer_id, comment_id, source, reason, reported_by)
+            VALUES ($1, $2, $3, $4, $5, $6)",
+            item.question_id.map(|id| id.0),
+            item.answer_id.map(|id| id.0),
+            item.comment_id.map(|id| id.0),
+            item.source.as_str(),
+            item.reason,
+            item.reported_by.map(|id| id.0),
+        )
+        .execute(&mut *conn)
+        .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Lists pending moderation items, oldest first
+    pub async fn get_moderation_queue(
+        &self,
+        limit: Option<u32>,
+        offset: u32,
+    ) -> Result<Vec<ModerationItem>, Error> {
+        let _timer = metrics::store_query("get_moderation_queue");
+        let mut conn = self.acquire().await?;
+        match sqlx::query_as!(
+            ModerationItemRow,
+            "SELECT id, question_id, answer_id, comment_id, source, reason, reported_by, status,
+                created_on
+            FROM moderation_items
+            WHERE status = 'pending'
+            ORDER BY created_on
+            LIMIT $1
+            OFFSET $2",
+            limit.map(i64::from),
+            i64::from(offset),
+        )
+        .try_map(ModerationItem::try_from)
+        .fetch_all(&mut *conn)
+        .await
+        {
+            Ok(items) => Ok(items),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn get_moderation_item(&self, item_id: i32) -> Result<ModerationItem, Error> {
+        let _timer = metrics::store_query("get_moderation_item");
+        let mut conn = self.acquire().await?;
+        match sqlx::query_as!(
+            ModerationItemRow,
+            "SELECT id, question_id, answer_id, comment_id, source, reason, reported_by, status,
+                created_on
+            FROM moderation_items
+            WHERE id = $1",
+            item_id,
+        )
+        .try_map(ModerationItem::try_from)
+        .fetch_optional(&mut *conn)
+        .await
+        {
+            Ok(Some(item)) => Ok(item),
+            Ok(None) => Err(Error::ModerationItemNotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Resolves the given item together with all other pending items about the same
+    /// question, answer or comment
+    pub async fn resolve_moderation_items(
+        &self,
+        item: &ModerationItem,
+        status: ModerationStatus,
+        moderator_account_id: &AccountId,
+    ) -> Result<bool, Error> {
+        let _timer = metrics::store_query("resolve_moderation_items");
+        let mut conn = self.acquire().await?;
+        match sqlx::query!(
+            "UPDATE moderation_items
+            SET status = $1, resolved_by = $2, resolved_on = NOW()
+            WHERE id = $3
+            OR (
+                status = 'pending'
+                AND question_id IS NOT DISTINCT FROM $4
+                AND answer_id IS NOT DISTINCT FROM $5
+                AND comment_id IS NOT DISTINCT FROM $6
+            )",
+            status.as_str(),
+            moderator_account_id.0,
+            item.id.0,
+            item.question_id.as_ref().map(|id| id.0),
+            item.answer_id.as_ref().map(|id| id.0),
+            item.comment_id.as_ref().map(|id| id.0),
+        )
+        .execute(&mut *conn)
+        .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn add_moderation_action(
+        &self,
+        action: NewModerationAction,
+    ) -> Result<ModerationAction, Error> {
+        let _timer = metrics::store_query("add_moderation_action");
+        let mut conn = self.acquire().await?;
+        match sqlx::query_scalar!(
+            "INSERT INTO moderation_actions
+                (item_id, moderator_account_id, action, note, previous_title, previous_content)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING created_on",
+            action.item_id.0,
+            action.moderator_account_id.0,
+            action.action.as_str(),
+            action.note,
+            action.previous_title,
+            action.previous_content,
+        )
+        .fetch_one(&mut *conn)
+        .await
+        {
+            Ok(created_on) => Ok(ModerationAction {
+                item_id: action.item_id,
+                moderator_account_id: action.moderator_account_id,
+                action: action.action,
+                note: action.note,
+                previous_title: action.previous_title,
+                previous_content: action.previous_content,
+                created_on,
+            }),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Audit trail of an item, oldest first
+    pub async fn get_moderation_actions(
+        &self,
+        item_id: i32,
+    ) -> Result<Vec<ModerationAction>, Error> {
+        let _timer = metrics::store_query("get_moderation_actions");
+        let mut conn = self.acquire().await?;
+        match sqlx::query_as!(
+            ModerationActionRow,
+            "SELECT item_id, moderator_account_id, action, note, previous_title, previous_content,
+                created_on
+            FROM moderation_actions
+            WHERE item_id = $1
+            ORDER BY created_on",
+            item_id,
+        )
+        .try_map(ModerationAction::try_from)
+        .fetch_all(&mut *conn)
+        .await
+        {
+            Ok(actions) => Ok(actions),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn set_question_visibility(
+        &self,
+        question_id: i32,
+        visibility: Visibility,
+    ) -> Result<bool, Error> {
+        let _timer = metrics::store_query("set_question_visibility");
+        let mut conn = self.acquire().await?;
+        match sqlx::query!(
+            "UPDATE questions SET visibility = $1 WHERE id = $2",
+            visibility.as_str(),
+            question_id,
+        )
+        .execute(&mut *conn)
+        .await
+        {
+            Ok(_) => {
+                Self::notify_feed(&mut conn, FeedEventKind::QuestionUpdated, question_id).await;
+                Ok(true)
+            }
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn set_answer_visibility(
+        &self,
+        answer_id: i32,
+        visibility: Visibility,
+    ) -> Result<bool, Error> {
+        let _timer = metrics::store_query("set_answer_visibility");
+        let mut conn = self.acquire().await?;
+        match sqlx::query!(
+            "UPDATE answers SET visibility = $1 WHERE id = $2",
+            visibility.as_str(),
+            answer_id,
+        )
+        .execute(&mut *conn)
+        .await
+        {
+            Ok(_) => {
+                Self::notify_feed(&mut conn, FeedEventKind::AnswerUpdated, answer_id).await;
+                Ok(true)
+            }
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Replaces title (if given) and content of a question.
+    /// Returns the previous title and content for the audit trail.
+    pub async fn edit_question_content(
+        &self,
+        question_id: i32,
+        title: Option<String>,
+        content: String,
+    ) -> Result<(String, String), Error> {
+        let _timer = metrics::store_query("edit_question_content");
+        let mut conn = self.acquire().await?;
+        match sqlx::query!(
+            "UPDATE questions SET title = COALESCE($1, questions.title), content = $2
+            FROM (SELECT id, title, content FROM questions WHERE id = $3 FOR UPDATE) AS previous
+            WHERE questions.id = previous.id
+            RETURNING previous.title, previous.content",
+            title,
+            content,
+            question_id,
+        )
+        .map(|row| (row.title, row.content))
+        .fetch_optional(&mut *conn)
+        .await
+        {
+            Ok(Some(previous)) => {
+                Self::notify_feed(&mut conn, FeedEventKind::QuestionUpdated, question_id).await;
+                Ok(previous)
+            }
+            Ok(None) => Err(Error::QuestionNotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Replaces the content of an answer. Returns the previous content for the audit trail.
+    pub async fn edit_answer_content(
+        &self,
+        answer_id: i32,
+        content: String,
+    ) -> Result<String, Error> {
+        let _timer = metrics::store_query("edit_answer_content");
+        let mut conn = self.acquire().await?;
+        match sqlx::query_scalar!(
+            "UPDATE answers SET content = $1
+            FROM (SELECT id, content FROM answers WHERE id = $2 FOR UPDATE) AS previous
+            WHERE answers.id = previous.id
+            RETURNING previous.content",
+            content,
+            answer_id,
+        )
+        .fetch_optional(&mut *conn)
+        .await
+        {
+            Ok(Some(previous)) => {
+                Self::notify_feed(&mut conn, FeedEventKind::AnswerUpdated, answer_id).await;
+                Ok(previous)
+            }
+            Ok(None) => Err(Error::AnswerNotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Comments are not published on the live feed, so no notification is sent
+    pub async fn set_comment_visibility(
+        &self,
+        comment_id: i32,
+        visibility: Visibility,
+    ) -> Result<bool, Error> {
+        let _timer = metrics::store_query("set_comment_visibility");
+        let mut conn = self.acquire().await?;
+        match sqlx::query!(
+            "UPDATE comments SET visibility = $1 WHERE id = $2",
+            visibility.as_str(),
+            comment_id,
+        )
+        .execute(&mut *conn)
+        .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Replaces the content of a comment. Returns the previous content for the audit trail.
+    pub async fn edit_comment_content(
+        &self,
+        comment_id: i32,
+        content: String,
+    ) -> Result<String, Error> {
+        let _timer = metrics::store_query("edit_comment_content");
+        let mut conn = self.acquire().await?;
+        match sqlx::query_scalar!(
+            "UPDATE comments SET content = $1
+            FROM (SELECT id, content FROM comments WHERE id = $2 FOR UPDATE) AS previous
+            WHERE comments.id = previous.id
+            RETURNING previous.content",
+            content,
+            comment_id,
+        )
+        .fetch_optional(&mut *conn)
+        .await
+        {
+            Ok(Some(previous)) => Ok(previous),
+            // The item is deleted together with its comment
+            Ok(None) => Err(Error::ModerationItemNotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Tells all instances about a new or updated post. The post is published on the live
+    /// feed once it is visible. Failures are logged, but do not fail the write.
+    async fn notify_feed(conn: &mut PgConnection, kind: FeedEventKind, id: i32) {
+        let payload = match serde_json::to_string(&FeedNotification { kind, id }) {
+            Ok(payload) => payload,
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return;
+            }
+        };
+
+        if let Err(e) = sqlx::query!("SELECT pg_notify($1, $2)", FEED_CHANNEL, payload)
+            .execute(conn)
+            .await
+        {
+            tracing::event!(tracing::Level::ERROR, "{:?}", e);
+        }
+    }
+
+    /// Loads the post of a feed notification. Returns `None` if it is not visible (anymore).
+    pub async fn get_feed_event(
+        &self,
+        notification: FeedNotification,
+    ) -> Result<Option<FeedEvent>, Error> {
+        let _timer = metrics::store_query("get_feed_event");
+        if !notification.kind.is_answer() {
+            return match self.get_latest_question(notification.id).await {
+                Ok(question) => Ok(Some(FeedEvent {
+                    kind: notification.kind,
+                    question_id: question.id.clone(),
+                    tags: question.tags.clone().unwrap_or_default(),
+                    question: Some(question),
+                    answer: None,
+                })),
+                Err(Error::QuestionNotFound) => Ok(None),
+                Err(e) => Err(e),
+            };
+        }
+
+        let mut conn = self.acquire().await?;
+        match sqlx::query!(
+            "SELECT answers.id, answers.content, answers.corresponding_question,
+                answers.account_id, accounts.display_name, accounts.avatar_url, questions.tags
+            FROM answers
+            JOIN questions ON questions.id = answers.corresponding_question
+            LEFT JOIN accounts ON accounts.id = answers.account_id
+            WHERE answers.id = $1
+                AND answers.visibility = 'visible' AND questions.visibility = 'visible'",
+            notification.id,
+        )
+        .map(|row| FeedEvent {
+            kind: notification.kind,
+            question_id: QuestionId(row.corresponding_question),
+            tags: row.tags,
+            question: None,
+            answer: Some(Answer::from(AnswerRow {
+                id: row.id,
+                content: row.content,
+                corresponding_question: row.corresponding_question,
+                account_id: row.account_id,
+                display_name: row.display_name,
+                avatar_url: row.avatar_url,
+            })),
+        })
+        .fetch_optional(&mut *conn)
+        .await
+        {
+            Ok(event) => Ok(event),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn add_webhook(
+        &self,
+        new_webhook: NewWebhook,
+        secret: String,
+        account_id: &AccountId,
+    ) -> Result<Webhook, Error> {
+        let _timer = metrics::store_query("add_webhook");
+        let mut conn = self.acquire().await?;
+        match sqlx::query_as!(
+            WebhookRow,
+            r#"INSERT INTO webhooks (account_id, url, secret, events)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, url, secret AS "secret?", events, created_on"#,
+            account_id.0,
+            new_webhook.url,
+            secret,
+            &new_webhook
+                .events
+                .iter()
+                .map(|event| event.as_str().to_string())
+                .collect::<Vec<_>>(),
+        )
+        .try_map(Webhook::try_from)
+        .fetch_one(&mut *conn)
+        .await
+        {
+            Ok(webhook) => Ok(webhook),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Webhooks of an account, without their secrets
+    pub async fn get_webhooks(&self, account_id: &AccountId) -> Result<Vec<Webhook>, Error> {
+        let _timer = metrics::store_query("get_webhooks");
+        let mut conn = self.acquire().await?;
+        match sqlx::query_as!(
+            WebhookRow,
+            "SELECT id, url, NULL::VARCHAR AS secret, events, created_on FROM webhooks
+            WHERE account_id = $1
+            ORDER BY created_on",
+            account_id.0,
+        )
+        .try_map(Webhook::try_from)
+        .fetch_all(&mut *conn)
+        .await
+        {
+            Ok(webhooks) => Ok(webhooks),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn is_webhook_owner(
+        &self,
+        webhook_id: i32,
+        account_id: &AccountId,
+    ) -> Result<bool, Error> {
+        let _timer = metrics::store_query("is_webhook_owner");
+        let mut conn = self.acquire().await?;
+        match sqlx::query!("SELECT account_id FROM webhooks WHERE id = $1", webhook_id)
+            .map(|row| AccountId(row.account_id))
+            .fetch_optional(&mut *conn)
+            .await
+        {
+            Ok(Some(owner)) => Ok(owner == *account_id),
+            Ok(None) => Err(Error::WebhookNotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Deletes a webhook of the account together with its deliveries
+    pub async fn delete_webhook(
+        &self,
+        webhook_id: i32,
+        account_id: &AccountId,
+    ) -> Result<bool, Error> {
+        let _timer = metrics::store_query("delete_webhook");
+        let mut conn = self.acquire().await?;
+        match sqlx::query!(
+            "DELETE FROM webhooks WHERE id = $1 AND account_id = $2",
+            webhook_id,
+            account_id.0,
+        )
+        .execute(&mut *conn)
+        .await
+        {
+            Ok(result) if result.rows_affected() == 0 => Err(Error::WebhookNotFound),
+            Ok(_) => Ok(true),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Delivery log of a webhook, newest first
+    pub async fn get_webhook_deliveries(
+        &self,
+        webhook_id: i32,
+        limit: Option<u32>,
+        offset: u32,
+    ) -> Result<Vec<WebhookDelivery>, Error> {
+        let _timer = metrics::store_query("get_webhook_deliveries");
+        let mut conn = self.acquire().await?;
+        match sqlx::query_as!(
+            WebhookDeliveryRow,
+            r#"SELECT webhook_deliveries.id, webhook_id, event, webhook_deliveries.payload,
+                webhook_deliveries.status, webhook_deliveries.attempts, last_status_code,
+                webhook_deliveries.last_error, jobs.run_at AS "next_attempt_on?",
+                webhook_deliveries.created_on, delivered_on
+            FROM webhook_deliveries
+            LEFT JOIN jobs ON jobs.kind = 'deliver_webhook' AND jobs.status = 'pending'
+                AND (jobs.payload->>'delivery_id')::integer = webhook_deliveries.id
+            WHERE webhook_id = $1
+            ORDER BY webhook_deliveries.created_on DESC
+            LIMIT $2
+            OFFSET $3"#,
+            webhook_id,
+            limit.map(i64::from),
+            i64::from(offset),
+        )
+        .try_map(WebhookDelivery::try_from)
+        .fetch_all(&mut *conn)
+        .await
+        {
+            Ok(deliveries) => Ok(deliveries),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Queues a delivery of `payload` to every webhook subscribed to `event`, each sent by a
+    /// `webhook::DeliverWebhook` job
+    pub async fn enqueue_webhook_deliveries(
+        &self,
+        event: WebhookEvent,
+        payload: serde_json::Value,
+    ) -> Result<bool, Error> {
+        let _timer = metrics::store_query("enqueue_webhook_deliveries");
+        let mut conn = self.acquire().await?;
+        match sqlx::query!(
+            "WITH deliveries AS (
+                INSERT INTO webhook_deliveries (webhook_id, event, payload)
+                SELECT id, $1::VARCHAR, $2 FROM webhooks WHERE $1 = ANY(events)
+                RETURNING id
+            )
+            INSERT INTO jobs (kind, payload)
+            SELECT 'deliver_webhook', jsonb_build_object('delivery_id', id) FROM deliveries",
+            event.as_str(),
+            payload,
+        )
+        .execute(&mut *conn)
+        .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Counts an attempt of a pending delivery and returns it with the webhook it goes to.
+    /// `None` if it was delivered or dead-lettered in the meantime, or its webhook deleted.
+    pub async fn claim_webhook_delivery(
+        &self,
+        delivery_id: &WebhookDeliveryId,
+    ) -> Result<Option<PendingDelivery>, Error> {
+        let _timer = metrics::store_query("claim_webhook_delivery");
+        let mut conn = self.acquire().await?;
+        match sqlx::query_as!(
+            PendingDeliveryRow,
+            r#"WITH claimed AS (
+                UPDATE webhook_deliveries
+                SET attempts = attempts + 1
+                WHERE id = $1 AND status = 'pending'
+                RETURNING id, webhook_id, event, payload, attempts, created_on
+            )
+            SELECT claimed.id AS "id!", claimed.event AS "event!",
+                claimed.payload AS "payload!", claimed.attempts AS "attempts!",
+                claimed.created_on AS "created_on!", webhooks.url, webhooks.secret
+            FROM claimed
+            JOIN webhooks ON webhooks.id = claimed.webhook_id"#,
+            delivery_id.0,
+        )
+        .try_map(PendingDelivery::try_from)
+        .fetch_optional(&mut *conn)
+        .await
+        {
+            Ok(delivery) => Ok(delivery),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Records the outcome of an attempt. Failed deliveries stay pending for the next attempt
+    /// of their job, or are dead-lettered with `give_up`.
+    pub async fn record_webhook_attempt(
+        &self,
+        delivery_id: &WebhookDeliveryId,
+        status_code: Option<i32>,
+        error: Option<String>,
+        give_up: bool,
+    ) -> Result<bool, Error> {
+        let _timer = metrics::store_query("record_webhook_attempt");
+        let mut conn = self.acquire().await?;
+        let status = match (&error, give_up) {
+            (None, _) => DeliveryStatus::Delivered,
+            (Some(_), false) => DeliveryStatus::Pending,
+            (Some(_), true) => DeliveryStatus::DeadLetter,
+        };
+
+        match sqlx::query!(
+            "UPDATE webhook_deliveries
+            SET status = $1::TEXT, last_status_code = $2, last_error = $3,
+                delivered_on = CASE WHEN $1 = 'delivered' THEN NOW() END
+            WHERE id = $4",
+            status.as_str(),
+            status_code,
+            error,
+            delivery_id.0,
+        )
+        .execute(&mut *conn)
+        .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Attempts a delivery of the webhook again right away, e.g. after it was dead-lettered
+    pub async fn retry_webhook_delivery(
+        &self,
+        webhook_id: i32,
+        delivery_id: i32,
+    ) -> Result<bool, Error> {
+        let _timer = metrics::store_query("retry_webhook_delivery");
+        let mut conn = self.acquire().await?;
+        // A job which is still queued for the delivery is moved up instead
+        match sqlx::query!(
+            "WITH retried AS (
+                UPDATE webhook_deliveries
+                SET status = 'pending', attempts = 0
+                WHERE id = $1 AND webhook_id = $2 AND status <> 'delivered'
+                RETURNING id
+            )
+            INSERT INTO jobs (kind, payload)
+            SELECT 'deliver_webhook', jsonb_build_object('delivery_id', id) FROM retried
+            ON CONFLICT (((payload->>'delivery_id')::integer))
+                WHERE kind = 'deliver_webhook' AND status IN ('pending', 'running')
+            DO UPDATE SET run_at = NOW(), attempts = 0",
+            delivery_id,
+            webhook_id,
+        )
+        .execute(&mut *conn)
+        .await
+        {
+            Ok(result) if result.rows_affected() == 0 => Err(Error::WebhookDeliveryNotFound),
+            Ok(_) => Ok(true),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Queues a job to run at `run_at`, given up after `max_attempts` or the attempts
+    /// configured for its kind. For recurring jobs nothing is queued while an occurrence with
+    /// the same `recurring_key` is pending or running.
+    pub async fn enqueue_job(
+        &self,
+        kind: &str,
+        payload: serde_json::Value,
+        run_at: NaiveDateTime,
+        max_attempts: Option<i32>,
+        recurring_key: Option<&str>,
+    ) -> Result<bool, Error> {
+        let _timer = metrics::store_query("enqueue_job");
+        let mut conn = self.acquire().await?;
+        match sqlx::query!(
+            "INSERT INTO jobs (kind, payload, run_at, max_attempts, recurring_key)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (recurring_key) WHERE status IN ('pending', 'running') DO NOTHING",
+            kind,
+            payload,
+            run_at,
+            max_attempts,
+            recurring_key,
+        )
+        .execute(&mut *conn)
+        .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Claims up to `limit` due jobs and counts the attempt. Jobs are locked for `lease_secs`,
+    /// afterwards they are claimed again, e.g. if the runner which claimed them crashed.
+    pub async fn claim_jobs(&self, limit: i64, lease_secs: u64) -> Result<Vec<ClaimedJob>, Error> {
+        let _timer = metrics::store_query("claim_jobs");
+        let mut conn = self.acquire().await?;
+        match sqlx::query_as!(
+            ClaimedJobRow,
+            "UPDATE jobs
+            SET status = 'running', attempts = attempts + 1,
+                locked_until = NOW() + make_interval(secs => $2)
+            WHERE id IN (
+                SELECT id FROM jobs
+                WHERE (status = 'pending' AND run_at <= NOW())
+                    OR (status = 'running' AND locked_until < NOW())
+                ORDER BY run_at
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, kind, payload, attempts, max_attempts, recurring_key",
+            limit,
+            lease_secs as f64,
+        )
+        .map(ClaimedJob::from)
+        .fetch_all(&mut *conn)
+        .await
+        {
+            Ok(jobs) => Ok(jobs),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Records the outcome of a job. Failed jobs run again after `retry_in_secs`,
+    /// or are given up if it is `None`.
+    pub async fn record_job_result(
+        &self,
+        job_id: &JobId,
+        error: Option<String>,
+        retry_in_secs: Option<u64>,
+    ) -> Result<bool, Error> {
+        let _timer = metrics::store_query("record_job_result");
+        let mut conn = self.acquire().await?;
+        let status = match (&error, retry_in_secs) {
+            (None, _) => JobStatus::Done,
+            (Some(_), Some(_)) => JobStatus::Pending,
+            (Some(_), None) => JobStatus::Failed,
+        };
+
+        match sqlx::query!(
+            "UPDATE jobs
+            SET status = $1::TEXT, last_error = $2, locked_until = NULL,
+                run_at = COALESCE(NOW() + make_interval(secs => $3), run_at),
+                finished_on = CASE WHEN $1 IN ('done', 'failed') THEN NOW() END
+            WHERE id = $4",
+            status.as_str(),
+            error,
+            retry_in_secs.map(|secs| secs as f64),
+            job_id.0,
+        )
+        .execute(&mut *conn)
+        .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Deletes finished jobs older than `older_than_secs`. Returns the number of deleted jobs.
+    pub async fn purge_finished_jobs(&self, older_than_secs: u64) -> Result<u64, Error> {
+        let _timer = metrics::store_query("purge_finished_jobs");
+        let mut conn = self.acquire().await?;
+        match sqlx::query!(
+            "DELETE FROM jobs
+            WHERE status IN ('done', 'failed')
+                AND finished_on < NOW() - make_interval(secs => $1)",
+            older_than_secs as f64,
+        )
+        .execute(&mut *conn)
+        .await
+        {
+            Ok(result) => Ok(result.rows_affected()),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Deletes rate limit buckets not used for `older_than_secs`, they are full again anyway.
+    /// Returns the number of deleted buckets.
+    pub async fn purge_rate_limit_buckets(&self, older_than_secs: u64) -> Result<u64, Error> {
+        let _timer = metrics::store_query("purge_rate_limit_buckets");
+        let mut conn = self.acquire().await?;
+        match sqlx::query!(
+            "DELETE FROM rate_limit_buckets WHERE updated_on < NOW() - make_interval(secs => $1)",
+            older_than_secs as f64,
+        )
+        .execute(&mut *conn)
+        .await
+        {
+            Ok(result) => Ok(result.rows_affected()),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Fails if the database cannot be reached
+    pub async fn ping(&self) -> Result<bool, Error> {
+        let _timer = metrics::store_query("ping");
+        let mut conn = self.acquire().await?;
+        match sqlx::query_scalar!("SELECT 1 AS one")
+            .fetch_one(&mut *conn)
+            .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Version of the latest successfully applied migration
+    pub async fn get_migration_version(&self) -> Result<Option<i64>, Error> {
+        let _timer = metrics::store_query("get_migration_version");
+        let mut conn = self.acquire().await?;
+        match sqlx::query_scalar!(
+            "SELECT MAX(version) AS version FROM _sqlx_migrations WHERE success = true"
+        )
+        .fetch_one(&mut *conn)
+        .await
         {
-            Ok(question) => Ok(question.is_some()),
+            Ok(version) => Ok(version),
             Err(e) => {
                 tracing::event!(tracing::Level::ERROR, "{:?}", e);
                 Err(Error::DatabaseQueryError(e))
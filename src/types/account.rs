@@ -1,6 +1,9 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use crate::types::answer::Answer;
+use crate::types::question::Question;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Session {
     pub exp: DateTime<Utc>,
@@ -8,12 +11,49 @@ pub struct Session {
     pub nbf: DateTime<Utc>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct AccountId(pub i32);
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Account {
     pub id: Option<AccountId>,
     pub email: String,
+    // Never send the password hash back to the client
+    #[serde(skip_serializing)]
     pub password: String,
 }
+
+/// Compact, public information about the account which wrote a question or an answer
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Author {
+    pub id: AccountId,
+    pub display_name: Option<String>,
+    pub avatar_url: Option<String>,
+}
+
+/// Public profile of an account, without email or password
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Profile {
+    pub id: AccountId,
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub avatar_url: Option<String>,
+    pub reputation: i32,
+    pub created_on: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UpdateProfile {
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub avatar_url: Option<String>,
+}
+
+/// Response of `GET /users/{id}`
+#[derive(Serialize, Debug, Clone)]
+pub struct UserDetails {
+    #[serde(flatten)]
+    pub profile: Profile,
+    pub questions: Vec<Question>,
+    pub answers: Vec<Answer>,
+}
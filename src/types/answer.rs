@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
 
+use crate::types::account::Author;
 use crate::types::question::QuestionId;
 
 #[derive(Deserialize, Debug, Clone, Serialize, PartialEq, Eq, Hash)]
@@ -10,6 +11,8 @@ pub struct Answer {
     pub id: AnswerId,
     pub content: String,
     pub question_id: QuestionId,
+    #[serde(skip_deserializing)]
+    pub author: Option<Author>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
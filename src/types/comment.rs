@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+
+use crate::types::account::Author;
+use crate::types::question::QuestionId;
+
+#[derive(Deserialize, Debug, Clone, Serialize, PartialEq, Eq, Hash)]
+pub struct CommentId(pub i32);
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Comment {
+    pub id: CommentId,
+    pub content: String,
+    pub question_id: QuestionId,
+    #[serde(skip_deserializing)]
+    pub author: Option<Author>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NewComment {
+    pub content: String,
+}
@@ -0,0 +1,72 @@
+use serde::{Deserialize, Serialize};
+
+use crate::types::answer::Answer;
+use crate::types::question::{Question, QuestionId};
+
+/// Postgres channel the store notifies about new and updated posts
+pub const FEED_CHANNEL: &str = "feed";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedEventKind {
+    QuestionCreated,
+    QuestionUpdated,
+    AnswerCreated,
+    AnswerUpdated,
+}
+
+impl FeedEventKind {
+    /// Name of the server-sent event
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FeedEventKind::QuestionCreated => "question_created",
+            FeedEventKind::QuestionUpdated => "question_updated",
+            FeedEventKind::AnswerCreated => "answer_created",
+            FeedEventKind::AnswerUpdated => "answer_updated",
+        }
+    }
+
+    pub fn is_answer(&self) -> bool {
+        matches!(
+            self,
+            FeedEventKind::AnswerCreated | FeedEventKind::AnswerUpdated
+        )
+    }
+}
+
+/// Payload of a `NOTIFY` on `FEED_CHANNEL`. Payloads are limited in size, so only the id of
+/// the question or answer is sent and every instance loads the post itself.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct FeedNotification {
+    pub kind: FeedEventKind,
+    pub id: i32,
+}
+
+/// New or updated question or answer streamed to clients of the live feed
+#[derive(Serialize, Debug, Clone)]
+pub struct FeedEvent {
+    pub kind: FeedEventKind,
+    pub question_id: QuestionId,
+    /// Tags of the question, also for answers
+    pub tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub question: Option<Question>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub answer: Option<Answer>,
+}
+
+/// Query parameters of the live feed endpoints
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct FeedFilter {
+    pub tag: Option<String>,
+    pub question_id: Option<i32>,
+}
+
+impl FeedFilter {
+    pub fn matches(&self, event: &FeedEvent) -> bool {
+        self.tag.as_ref().is_none_or(|tag| event.tags.contains(tag))
+            && self
+                .question_id
+                .is_none_or(|question_id| event.question_id.0 == question_id)
+    }
+}
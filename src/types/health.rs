@@ -0,0 +1,27 @@
+use serde::Serialize;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Ok,
+    Unavailable,
+}
+
+/// Response of `GET /health/ready`
+#[derive(Serialize, Debug, Clone)]
+pub struct Readiness {
+    pub ready: bool,
+    pub database: CheckStatus,
+    /// Only checked if `health.check_profanity` is enabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profanity: Option<CheckStatus>,
+}
+
+/// Response of `GET /version`
+#[derive(Serialize, Debug, Clone)]
+pub struct VersionInfo {
+    pub version: &'static str,
+    pub git_hash: &'static str,
+    /// Latest applied migration, `None` if the database is unavailable or not migrated
+    pub migration: Option<i64>,
+}
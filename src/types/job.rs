@@ -0,0 +1,54 @@
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+#[derive(Deserialize, Debug, Clone, Serialize, PartialEq, Eq, Hash)]
+pub struct JobId(pub i64);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Pending,
+    Running,
+    Done,
+    /// Given up after too many failed attempts
+    Failed,
+}
+
+impl JobStatus {
+    /// Value stored in the `status` column of `jobs`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Pending => "pending",
+            JobStatus::Running => "running",
+            JobStatus::Done => "done",
+            JobStatus::Failed => "failed",
+        }
+    }
+}
+
+impl FromStr for JobStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(JobStatus::Pending),
+            "running" => Ok(JobStatus::Running),
+            "done" => Ok(JobStatus::Done),
+            "failed" => Ok(JobStatus::Failed),
+            _ => Err(format!("Unknown job status {}", s)),
+        }
+    }
+}
+
+/// Job claimed by a runner
+#[derive(Debug, Clone)]
+pub struct ClaimedJob {
+    pub id: JobId,
+    pub kind: String,
+    pub payload: serde_json::Value,
+    pub attempts: i32,
+    /// `None` for the attempts configured for the kind
+    pub max_attempts: Option<i32>,
+    /// Set for occurrences of recurring jobs, at most one occurrence per key is queued
+    pub recurring_key: Option<String>,
+}
@@ -1,4 +1,13 @@
 pub mod account;
 pub mod answer;
+pub mod comment;
+pub mod feed;
+pub mod health;
+pub mod job;
+pub mod moderation;
+pub mod notification;
 pub mod pagination;
 pub mod question;
+pub mod reputation;
+pub mod vote;
+pub mod webhook;
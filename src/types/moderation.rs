@@ -0,0 +1,192 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use crate::types::account::AccountId;
+use crate::types::answer::AnswerId;
+use crate::types::comment::CommentId;
+use crate::types::question::QuestionId;
+
+#[derive(Deserialize, Debug, Clone, Serialize, PartialEq, Eq, Hash)]
+pub struct ModerationItemId(pub i32);
+
+/// Whether a question, answer or comment is shown to other users
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Visibility {
+    Visible,
+    /// Waiting for a moderator
+    Pending,
+    Removed,
+}
+
+impl Visibility {
+    /// Value stored in the `visibility` column of `questions`, `answers` and `comments`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Visibility::Visible => "visible",
+            Visibility::Pending => "pending",
+            Visibility::Removed => "removed",
+        }
+    }
+}
+
+/// How an item ended up in the moderation queue
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ModerationSource {
+    Flag,
+    ProfanityFilter,
+    SpamFilter,
+}
+
+impl ModerationSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ModerationSource::Flag => "flag",
+            ModerationSource::ProfanityFilter => "profanity_filter",
+            ModerationSource::SpamFilter => "spam_filter",
+        }
+    }
+}
+
+impl FromStr for ModerationSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "flag" => Ok(ModerationSource::Flag),
+            "profanity_filter" => Ok(ModerationSource::ProfanityFilter),
+            "spam_filter" => Ok(ModerationSource::SpamFilter),
+            _ => Err(format!("Unknown moderation source {}", s)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ModerationStatus {
+    Pending,
+    Approved,
+    Edited,
+    Removed,
+}
+
+impl ModerationStatus {
+    /// Value stored in the `status` column of `moderation_items` and the `action` column of
+    /// `moderation_actions`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ModerationStatus::Pending => "pending",
+            ModerationStatus::Approved => "approved",
+            ModerationStatus::Edited => "edited",
+            ModerationStatus::Removed => "removed",
+        }
+    }
+}
+
+impl FromStr for ModerationStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(ModerationStatus::Pending),
+            "approved" => Ok(ModerationStatus::Approved),
+            "edited" => Ok(ModerationStatus::Edited),
+            "removed" => Ok(ModerationStatus::Removed),
+            _ => Err(format!("Unknown moderation status {}", s)),
+        }
+    }
+}
+
+/// Question, answer or comment waiting for review. Exactly one of `question_id`, `answer_id`
+/// and `comment_id` is set.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ModerationItem {
+    pub id: ModerationItemId,
+    pub question_id: Option<QuestionId>,
+    pub answer_id: Option<AnswerId>,
+    pub comment_id: Option<CommentId>,
+    pub source: ModerationSource,
+    pub reason: Option<String>,
+    pub reported_by: Option<AccountId>,
+    pub status: ModerationStatus,
+    pub created_on: NaiveDateTime,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewModerationItem {
+    pub question_id: Option<QuestionId>,
+    pub answer_id: Option<AnswerId>,
+    pub comment_id: Option<CommentId>,
+    pub source: ModerationSource,
+    pub reason: Option<String>,
+    pub reported_by: Option<AccountId>,
+}
+
+/// Request body of `POST /questions/{id}/flag` and `POST /answers/{id}/flag`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NewFlag {
+    pub reason: String,
+}
+
+/// What a moderator decided about a queued item
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ModerationDecision {
+    Approve,
+    /// Replace the content and publish it. `title` is ignored for answers and comments.
+    Edit {
+        title: Option<String>,
+        content: String,
+    },
+    Remove,
+}
+
+impl ModerationDecision {
+    pub fn status(&self) -> ModerationStatus {
+        match self {
+            ModerationDecision::Approve => ModerationStatus::Approved,
+            ModerationDecision::Edit { .. } => ModerationStatus::Edited,
+            ModerationDecision::Remove => ModerationStatus::Removed,
+        }
+    }
+
+    pub fn visibility(&self) -> Visibility {
+        match self {
+            ModerationDecision::Approve | ModerationDecision::Edit { .. } => Visibility::Visible,
+            ModerationDecision::Remove => Visibility::Removed,
+        }
+    }
+}
+
+/// Request body of `POST /moderation/queue/{id}/resolve`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ModerationResolution {
+    #[serde(flatten)]
+    pub decision: ModerationDecision,
+    pub note: Option<String>,
+}
+
+/// Audit trail entry of a moderator decision
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ModerationAction {
+    pub item_id: ModerationItemId,
+    pub moderator_account_id: AccountId,
+    pub action: ModerationStatus,
+    pub note: Option<String>,
+    /// Title and content before an edit
+    pub previous_title: Option<String>,
+    pub previous_content: Option<String>,
+    pub created_on: NaiveDateTime,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewModerationAction {
+    pub item_id: ModerationItemId,
+    pub moderator_account_id: AccountId,
+    pub action: ModerationStatus,
+    pub note: Option<String>,
+    pub previous_title: Option<String>,
+    pub previous_content: Option<String>,
+}
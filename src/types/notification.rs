@@ -0,0 +1,51 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use crate::types::answer::AnswerId;
+use crate::types::comment::CommentId;
+use crate::types::question::QuestionId;
+
+#[derive(Deserialize, Debug, Clone, Serialize, PartialEq, Eq, Hash)]
+pub struct NotificationId(pub i32);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    NewAnswer,
+    NewComment,
+}
+
+impl NotificationKind {
+    /// Value stored in the `kind` column of `notifications`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationKind::NewAnswer => "new_answer",
+            NotificationKind::NewComment => "new_comment",
+        }
+    }
+}
+
+impl FromStr for NotificationKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "new_answer" => Ok(NotificationKind::NewAnswer),
+            "new_comment" => Ok(NotificationKind::NewComment),
+            _ => Err(format!("Unknown notification kind {}", s)),
+        }
+    }
+}
+
+/// Notification about activity on a followed question or tag
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Notification {
+    pub id: NotificationId,
+    pub kind: NotificationKind,
+    pub question_id: QuestionId,
+    pub answer_id: Option<AnswerId>,
+    pub comment_id: Option<CommentId>,
+    pub read: bool,
+    pub created_on: NaiveDateTime,
+}
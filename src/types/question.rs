@@ -1,4 +1,8 @@
+use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use crate::types::account::Author;
 
 #[derive(Deserialize, Debug, Clone, Serialize, PartialEq, Eq, Hash)]
 pub struct QuestionId(pub i32);
@@ -9,6 +13,18 @@ pub struct Question {
     pub title: String,
     pub content: String,
     pub tags: Option<Vec<String>>,
+    // Filled in by the store, clients cannot set the author
+    #[serde(skip_deserializing)]
+    pub author: Option<Author>,
+    // `null` while the question is open
+    #[serde(skip_deserializing)]
+    pub closed: Option<QuestionClosure>,
+    // Only known when the question was requested by an authenticated account
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub bookmarked: Option<bool>,
+    // Latest change of the question or its author, sent as `Last-Modified` instead
+    #[serde(skip)]
+    pub updated_on: Option<NaiveDateTime>,
 }
 
 #[derive(Deserialize, Debug, Clone, Serialize)]
@@ -17,3 +33,53 @@ pub struct NewQuestion {
     pub content: String,
     pub tags: Option<Vec<String>>,
 }
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CloseReason {
+    Duplicate,
+    OffTopic,
+    Resolved,
+}
+
+impl CloseReason {
+    /// Value stored in the `close_reason` column of `questions`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CloseReason::Duplicate => "duplicate",
+            CloseReason::OffTopic => "off_topic",
+            CloseReason::Resolved => "resolved",
+        }
+    }
+}
+
+impl FromStr for CloseReason {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "duplicate" => Ok(CloseReason::Duplicate),
+            "off_topic" => Ok(CloseReason::OffTopic),
+            "resolved" => Ok(CloseReason::Resolved),
+            _ => Err(format!("Unknown close reason {}", s)),
+        }
+    }
+}
+
+/// Why and when a question was closed
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QuestionClosure {
+    pub reason: CloseReason,
+    pub message: Option<String>,
+    /// Canonical question if closed as a duplicate
+    pub duplicate_of: Option<QuestionId>,
+    pub closed_on: NaiveDateTime,
+}
+
+/// Request body of `POST /questions/{id}/close`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CloseQuestion {
+    pub reason: CloseReason,
+    pub message: Option<String>,
+    pub duplicate_of: Option<QuestionId>,
+}
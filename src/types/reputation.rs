@@ -0,0 +1,100 @@
+use serde::{Deserialize, Serialize};
+
+use crate::types::account::AccountId;
+
+/// Kinds of events which change the reputation of an account
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReputationEventKind {
+    QuestionUpvoted,
+    QuestionDownvoted,
+    AnswerUpvoted,
+    AnswerDownvoted,
+    AnswerAccepted,
+}
+
+impl ReputationEventKind {
+    /// Points credited to the receiving account
+    pub fn points(&self) -> i32 {
+        match self {
+            ReputationEventKind::QuestionUpvoted => 5,
+            ReputationEventKind::QuestionDownvoted => -2,
+            ReputationEventKind::AnswerUpvoted => 10,
+            ReputationEventKind::AnswerDownvoted => -2,
+            ReputationEventKind::AnswerAccepted => 15,
+        }
+    }
+
+    /// Value stored in the `kind` column of `reputation_events`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReputationEventKind::QuestionUpvoted => "question_upvoted",
+            ReputationEventKind::QuestionDownvoted => "question_downvoted",
+            ReputationEventKind::AnswerUpvoted => "answer_upvoted",
+            ReputationEventKind::AnswerDownvoted => "answer_downvoted",
+            ReputationEventKind::AnswerAccepted => "answer_accepted",
+        }
+    }
+}
+
+/// Entry of the reputation ledger. `accounts.reputation` is the sum of all entries of an account
+/// and can always be recomputed from the ledger.
+#[derive(Debug, Clone)]
+pub struct ReputationEvent {
+    /// Account receiving the points
+    pub account_id: AccountId,
+    pub kind: ReputationEventKind,
+    pub points: i32,
+    /// Account whose action caused the event, e.g. the voter
+    pub actor_account_id: AccountId,
+    pub question_id: Option<i32>,
+    pub answer_id: Option<i32>,
+}
+
+impl ReputationEvent {
+    pub fn new(
+        kind: ReputationEventKind,
+        account_id: AccountId,
+        actor_account_id: AccountId,
+        question_id: Option<i32>,
+        answer_id: Option<i32>,
+    ) -> Self {
+        // Nobody earns reputation from their own actions, e.g. accepting their own answer
+        let points = if account_id == actor_account_id {
+            0
+        } else {
+            kind.points()
+        };
+
+        ReputationEvent {
+            account_id,
+            kind,
+            points,
+            actor_account_id,
+            question_id,
+            answer_id,
+        }
+    }
+}
+
+/// Actions which require a minimum reputation. Moderators may perform all of them.
+#[derive(Debug, Clone, Copy)]
+pub enum Privilege {
+    VoteDown,
+    Comment,
+    EditOthersPosts,
+    CloseQuestions,
+    ReviewModerationQueue,
+}
+
+impl Privilege {
+    pub fn required_reputation(&self) -> i32 {
+        match self {
+            Privilege::VoteDown => 125,
+            Privilege::Comment => 50,
+            Privilege::EditOthersPosts => 2000,
+            Privilege::CloseQuestions => 3000,
+            Privilege::ReviewModerationQueue => 10000,
+        }
+    }
+}
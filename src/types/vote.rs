@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+
+use crate::types::reputation::ReputationEventKind;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VoteDirection {
+    Up,
+    Down,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Vote {
+    pub direction: VoteDirection,
+}
+
+impl VoteDirection {
+    /// Value stored in the `value` column of the votes tables
+    pub fn value(&self) -> i16 {
+        match self {
+            VoteDirection::Up => 1,
+            VoteDirection::Down => -1,
+        }
+    }
+
+    pub fn question_event_kind(&self) -> ReputationEventKind {
+        match self {
+            VoteDirection::Up => ReputationEventKind::QuestionUpvoted,
+            VoteDirection::Down => ReputationEventKind::QuestionDownvoted,
+        }
+    }
+
+    pub fn answer_event_kind(&self) -> ReputationEventKind {
+        match self {
+            VoteDirection::Up => ReputationEventKind::AnswerUpvoted,
+            VoteDirection::Down => ReputationEventKind::AnswerDownvoted,
+        }
+    }
+}
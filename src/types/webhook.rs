@@ -0,0 +1,121 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+#[derive(Deserialize, Debug, Clone, Serialize, PartialEq, Eq, Hash)]
+pub struct WebhookId(pub i32);
+
+#[derive(Deserialize, Debug, Clone, Serialize, PartialEq, Eq, Hash)]
+pub struct WebhookDeliveryId(pub i32);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+    QuestionCreated,
+    AnswerAdded,
+    AnswerAccepted,
+}
+
+impl WebhookEvent {
+    /// Value stored in the `events` column of `webhooks` and sent as `X-Webhook-Event`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEvent::QuestionCreated => "question_created",
+            WebhookEvent::AnswerAdded => "answer_added",
+            WebhookEvent::AnswerAccepted => "answer_accepted",
+        }
+    }
+}
+
+impl FromStr for WebhookEvent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "question_created" => Ok(WebhookEvent::QuestionCreated),
+            "answer_added" => Ok(WebhookEvent::AnswerAdded),
+            "answer_accepted" => Ok(WebhookEvent::AnswerAccepted),
+            _ => Err(format!("Unknown webhook event {}", s)),
+        }
+    }
+}
+
+/// URL an account registered to receive events on
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Webhook {
+    pub id: WebhookId,
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+    /// Key of the `X-Webhook-Signature` HMAC, only sent back when the webhook is created
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    pub created_on: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NewWebhook {
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    Pending,
+    Delivered,
+    /// Given up after too many failed attempts
+    DeadLetter,
+}
+
+impl DeliveryStatus {
+    /// Value stored in the `status` column of `webhook_deliveries`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryStatus::Pending => "pending",
+            DeliveryStatus::Delivered => "delivered",
+            DeliveryStatus::DeadLetter => "dead_letter",
+        }
+    }
+}
+
+impl FromStr for DeliveryStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(DeliveryStatus::Pending),
+            "delivered" => Ok(DeliveryStatus::Delivered),
+            "dead_letter" => Ok(DeliveryStatus::DeadLetter),
+            _ => Err(format!("Unknown delivery status {}", s)),
+        }
+    }
+}
+
+/// Event to be sent to a webhook, with the outcome of the last attempt
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WebhookDelivery {
+    pub id: WebhookDeliveryId,
+    pub webhook_id: WebhookId,
+    pub event: WebhookEvent,
+    pub payload: serde_json::Value,
+    pub status: DeliveryStatus,
+    pub attempts: i32,
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    /// When the queued job attempts the delivery again
+    pub next_attempt_on: Option<NaiveDateTime>,
+    pub created_on: NaiveDateTime,
+    pub delivered_on: Option<NaiveDateTime>,
+}
+
+/// Delivery claimed by a `DeliverWebhook` job, with what is needed to send it
+#[derive(Debug, Clone)]
+pub struct PendingDelivery {
+    pub id: WebhookDeliveryId,
+    pub event: WebhookEvent,
+    pub payload: serde_json::Value,
+    pub attempts: i32,
+    pub url: String,
+    pub secret: String,
+    pub created_on: NaiveDateTime,
+}
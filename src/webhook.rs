@@ -0,0 +1,462 @@
+use futures_util::future::{BoxFuture, FutureExt};
+use handle_error::Error;
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use reqwest::{redirect, Client, Url};
+use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
+use reqwest_retry::{policies::ExponentialBackoff, RetryTransientMiddleware};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use warp::hyper::client::connect::dns::Name;
+
+use crate::config::WebhookConfig;
+use crate::jobs::Job;
+use crate::store::Store;
+use crate::types::webhook::{PendingDelivery, WebhookDeliveryId};
+
+/// Generates the key a webhook's payloads are signed with
+pub fn generate_secret() -> String {
+    hex::encode(rand::thread_rng().gen::<[u8; 32]>())
+}
+
+/// Hex encoded HMAC-SHA256 of `body`, sent as `X-Webhook-Signature: sha256=<signature>`
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Whether webhooks may be sent to `ip`. Loopback, private, link-local and other special
+/// purpose addresses are refused, webhooks would let any account reach internal services.
+fn is_public_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ipv4(ip),
+            None => is_public_ipv6(ip),
+        },
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        // "This network", shared address space of carrier-grade NAT, benchmarking and reserved
+        || a == 0
+        || (a == 100 && (64..128).contains(&b))
+        || (a == 198 && (18..20).contains(&b))
+        || a >= 240)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // Unique local, link-local and documentation addresses
+        || (first & 0xfe00) == 0xfc00
+        || (first & 0xffc0) == 0xfe80
+        || (first == 0x2001 && ip.segments()[1] == 0x0db8))
+}
+
+/// IP address of `url` if its host is not a name
+fn host_ip(url: &Url) -> Option<IpAddr> {
+    let host = url.host_str()?;
+    host.trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse()
+        .ok()
+}
+
+/// Resolves only to allowed addresses, public ones outside of tests, names pointing at
+/// internal hosts fail to resolve. Checking the addresses actually connected to also covers
+/// names which resolve differently between registration and delivery.
+struct CheckedResolver {
+    is_allowed: fn(IpAddr) -> bool,
+}
+
+impl Resolve for CheckedResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        let is_allowed = self.is_allowed;
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> =
+                tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+            if addrs.is_empty() || !addrs.iter().all(|addr| is_allowed(addr.ip())) {
+                return Err(format!("{} does not resolve to a public address", host).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Whether webhooks can be registered for `url`, an http or https URL of a host with public
+/// addresses only
+pub async fn is_allowed_url(url: &str) -> bool {
+    let url = match Url::parse(url) {
+        Ok(url) if url.scheme() == "http" || url.scheme() == "https" => url,
+        _ => return false,
+    };
+    if let Some(ip) = host_ip(&url) {
+        return is_public_address(ip);
+    }
+    match url.host_str().map(Name::from_str) {
+        Some(Ok(name)) => CheckedResolver {
+            is_allowed: is_public_address,
+        }
+        .resolve(name)
+        .await
+        .is_ok(),
+        _ => false,
+    }
+}
+
+/// Outcome of sending a delivery once, as recorded by `Store::record_webhook_attempt`
+#[derive(Debug)]
+struct Attempt {
+    status_code: Option<i32>,
+    error: Option<String>,
+    /// Whether the delivery is dead-lettered instead of attempted again
+    give_up: bool,
+}
+
+impl Attempt {
+    /// Fails if the delivery is to be attempted again, so the job runner retries it with
+    /// backoff
+    fn into_result(self) -> Result<(), Error> {
+        match self.error {
+            Some(error) if !self.give_up => Err(Error::WebhookDeliveryFailed(error)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Sends webhook deliveries, shared by all `DeliverWebhook` jobs
+#[derive(Clone)]
+pub struct WebhookClient {
+    client: ClientWithMiddleware,
+    max_attempts: i32,
+    is_allowed: fn(IpAddr) -> bool,
+}
+
+impl WebhookClient {
+    pub fn new(config: WebhookConfig) -> Self {
+        Self::with_address_check(config, is_public_address)
+    }
+
+    /// Client sending to the addresses `is_allowed` accepts, tests allow loopback addresses
+    /// to reach a stand-in receiver
+    fn with_address_check(config: WebhookConfig, is_allowed: fn(IpAddr) -> bool) -> Self {
+        let client = Client::builder()
+            .timeout(Duration::from_millis(config.timeout_ms))
+            // Redirects could lead to internal hosts, they are reported as unexpected status
+            .redirect(redirect::Policy::none())
+            .dns_resolver(Arc::new(CheckedResolver { is_allowed }))
+            .build()
+            .expect("Cannot build webhook HTTP client");
+        let retry_policy = ExponentialBackoff::builder().build_with_max_retries(config.max_retries);
+        let client = ClientBuilder::new(client)
+            .with(RetryTransientMiddleware::new_with_policy(retry_policy))
+            .build();
+
+        WebhookClient {
+            client,
+            max_attempts: config.max_attempts,
+            is_allowed,
+        }
+    }
+
+    /// Sends `delivery` and records the outcome. Fails if the delivery is to be attempted
+    /// again, so the job runner retries it with backoff.
+    async fn deliver(&self, store: &Store, delivery: PendingDelivery) -> Result<(), Error> {
+        let attempt = self.attempt(&delivery).await;
+        store
+            .record_webhook_attempt(
+                &delivery.id,
+                attempt.status_code,
+                attempt.error.clone(),
+                attempt.give_up,
+            )
+            .await?;
+        attempt.into_result()
+    }
+
+    /// Sends `delivery` once, without recording the outcome
+    async fn attempt(&self, delivery: &PendingDelivery) -> Attempt {
+        let body = serde_json::json!({
+            "id": delivery.id,
+            "event": delivery.event,
+            "created_on": delivery.created_on,
+            "data": delivery.payload,
+        })
+        .to_string();
+
+        // IP addresses are connected to without resolving, they are checked here instead
+        let url = match Url::parse(&delivery.url) {
+            Ok(url) if host_ip(&url).is_none_or(self.is_allowed) => url,
+            _ => {
+                tracing::event!(
+                    tracing::Level::WARN,
+                    "Webhook delivery {} refused, {} is not a public URL",
+                    delivery.id.0,
+                    delivery.url
+                );
+                return Attempt {
+                    status_code: None,
+                    error: Some("Not a public address".to_string()),
+                    give_up: true,
+                };
+            }
+        };
+
+        let res = self
+            .client
+            .post(url)
+            .header("Content-Type", "application/json")
+            .header("X-Webhook-Event", delivery.event.as_str())
+            .header("X-Webhook-Delivery", delivery.id.0)
+            .header(
+                "X-Webhook-Signature",
+                format!("sha256={}", sign(&delivery.secret, body.as_bytes())),
+            )
+            .body(body)
+            .send()
+            .await;
+
+        let (status_code, error) = match res {
+            Ok(res) if res.status().is_success() => (Some(res.status().as_u16() as i32), None),
+            Ok(res) => (
+                Some(res.status().as_u16() as i32),
+                Some(format!("Unexpected status {}", res.status())),
+            ),
+            Err(e) => (None, Some(e.to_string())),
+        };
+
+        let give_up = error.is_some() && delivery.attempts >= self.max_attempts;
+        if give_up {
+            tracing::event!(
+                tracing::Level::WARN,
+                "Webhook delivery {} dead-lettered after {} attempts",
+                delivery.id.0,
+                delivery.attempts
+            );
+        }
+
+        Attempt {
+            status_code,
+            error,
+            give_up,
+        }
+    }
+}
+
+/// Sends a queued webhook delivery. Queued by `Store::enqueue_webhook_deliveries`, which
+/// builds the payload in SQL.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeliverWebhook {
+    pub delivery_id: WebhookDeliveryId,
+}
+
+impl Job for DeliverWebhook {
+    const KIND: &'static str = "deliver_webhook";
+
+    type State = WebhookClient;
+
+    fn run(self, store: Store, client: WebhookClient) -> BoxFuture<'static, Result<(), Error>> {
+        async move {
+            match store.claim_webhook_delivery(&self.delivery_id).await? {
+                Some(delivery) => client.deliver(&store, delivery).await,
+                // Delivered, dead-lettered or deleted with its webhook
+                None => Ok(()),
+            }
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::webhook::WebhookEvent;
+    use tokio::sync::mpsc;
+    use warp::http::{HeaderMap, StatusCode};
+    use warp::hyper::body::Bytes;
+    use warp::Filter;
+
+    const SECRET: &str = "secret";
+
+    /// Client allowing loopback addresses, without retries of transient errors
+    fn client() -> WebhookClient {
+        WebhookClient::with_address_check(config(), |ip| ip.is_loopback())
+    }
+
+    fn config() -> WebhookConfig {
+        WebhookConfig {
+            max_retries: 0,
+            max_attempts: 3,
+            ..WebhookConfig::default()
+        }
+    }
+
+    fn delivery(url: String, attempts: i32) -> PendingDelivery {
+        PendingDelivery {
+            id: WebhookDeliveryId(1),
+            event: WebhookEvent::QuestionCreated,
+            payload: serde_json::json!({ "id": 1 }),
+            attempts,
+            url,
+            secret: SECRET.to_string(),
+            created_on: chrono::NaiveDateTime::default(),
+        }
+    }
+
+    /// Receiver on 127.0.0.1 answering every delivery with `status`. Passes on the headers
+    /// and body of the deliveries it received.
+    fn stand_in(status: StatusCode) -> (String, mpsc::UnboundedReceiver<(HeaderMap, Bytes)>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let route = warp::post()
+            .and(warp::header::headers_cloned())
+            .and(warp::body::bytes())
+            .map(move |headers, body| {
+                let _ = tx.send((headers, body));
+                warp::reply::with_status("", status)
+            });
+        let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        (format!("http://{}/hook", addr), rx)
+    }
+
+    #[tokio::test]
+    async fn delivery_is_signed_and_status_recorded() {
+        let (url, mut received) = stand_in(StatusCode::NO_CONTENT);
+        let attempt = client().attempt(&delivery(url, 1)).await;
+
+        assert_eq!(attempt.status_code, Some(204));
+        assert_eq!(attempt.error, None);
+        assert!(attempt.into_result().is_ok());
+
+        let (headers, body) = received.recv().await.unwrap();
+        assert_eq!(headers["X-Webhook-Event"], "question_created");
+        assert_eq!(headers["X-Webhook-Delivery"], "1");
+        assert_eq!(
+            headers["X-Webhook-Signature"],
+            format!("sha256={}", sign(SECRET, &body)).as_str()
+        );
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["data"], serde_json::json!({ "id": 1 }));
+    }
+
+    #[tokio::test]
+    async fn failed_attempt_is_retried() {
+        let (url, _received) = stand_in(StatusCode::INTERNAL_SERVER_ERROR);
+        let attempt = client().attempt(&delivery(url, 1)).await;
+
+        assert_eq!(attempt.status_code, Some(500));
+        assert!(attempt.error.is_some());
+        assert!(!attempt.give_up);
+        assert!(attempt.into_result().is_err());
+    }
+
+    #[tokio::test]
+    async fn unreachable_receiver_is_retried() {
+        // Nothing listens on the port once the listener is dropped
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let attempt = client()
+            .attempt(&delivery(format!("http://{}/hook", addr), 2))
+            .await;
+
+        assert_eq!(attempt.status_code, None);
+        assert!(attempt.error.is_some());
+        assert!(attempt.into_result().is_err());
+    }
+
+    #[tokio::test]
+    async fn last_failed_attempt_is_dead_lettered() {
+        let (url, _received) = stand_in(StatusCode::INTERNAL_SERVER_ERROR);
+        let attempt = client().attempt(&delivery(url, 3)).await;
+
+        assert_eq!(attempt.status_code, Some(500));
+        assert!(attempt.give_up);
+        assert!(attempt.into_result().is_ok());
+    }
+
+    #[tokio::test]
+    async fn internal_receiver_is_refused() {
+        let (url, mut received) = stand_in(StatusCode::OK);
+        let attempt = WebhookClient::new(config())
+            .attempt(&delivery(url, 1))
+            .await;
+
+        assert_eq!(attempt.status_code, None);
+        assert!(attempt.give_up);
+        assert!(received.try_recv().is_err());
+    }
+
+    fn is_public(ip: &str) -> bool {
+        is_public_address(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn internal_addresses_are_not_public() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "0.0.0.0",
+            "100.64.0.1",
+            "255.255.255.255",
+            "::1",
+            "::",
+            "fe80::1",
+            "fd00::1",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+        ] {
+            assert!(!is_public(ip), "{} is public", ip);
+        }
+    }
+
+    #[test]
+    fn public_addresses_are_allowed() {
+        for ip in [
+            "93.184.216.34",
+            "1.1.1.1",
+            "2606:4700:4700::1111",
+            "::ffff:8.8.8.8",
+        ] {
+            assert!(is_public(ip), "{} is not public", ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn urls_of_internal_hosts_are_refused() {
+        for url in [
+            "http://127.0.0.1:8080/hook",
+            "http://[::1]/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://localhost/hook",
+            "ftp://93.184.216.34/hook",
+            "not a url",
+        ] {
+            assert!(!is_allowed_url(url).await, "{} is allowed", url);
+        }
+        assert!(is_allowed_url("https://93.184.216.34/hook").await);
+    }
+}